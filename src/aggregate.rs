@@ -0,0 +1,453 @@
+use crate::column_ref::ColumnRef;
+use crate::value::{Value, ValueType};
+use anyhow::{ensure, Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+/// Grouping of the output records, producing one record for each group
+/// with the values of the grouping columns followed by the aggregates
+#[derive(Deserialize)]
+pub struct Group {
+    /// Columns in the output of the rules to group the records by
+    by: Vec<ColumnRef>,
+    aggregates: Vec<Aggregate>,
+}
+
+/// A column computed from the values of all the records in a group
+#[derive(Deserialize)]
+#[serde(try_from = "AggregateParams")]
+pub struct Aggregate {
+    header: String,
+    function: Function,
+    /// Column in the output of the rules to aggregate, which is absent for `count`
+    column: Option<ColumnRef>,
+    /// Separator of values for `join-distinct`
+    sep: Option<String>,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Function {
+    /// Number of records
+    Count,
+    /// Number of non-empty values
+    CountNonEmpty,
+    Sum,
+    Mean,
+    /// Minimum value, compared as numbers if all the values are numbers
+    Min,
+    /// Maximum value, compared as numbers if all the values are numbers
+    Max,
+    /// Number of distinct non-empty values
+    CountDistinct,
+    /// Distinct non-empty values joined in the order they first appear
+    JoinDistinct,
+}
+
+#[derive(Deserialize)]
+struct AggregateParams {
+    header: String,
+    function: Function,
+    column: Option<ColumnRef>,
+    sep: Option<String>,
+}
+
+impl Function {
+    /// Name of the function in the transformation file
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Count => "count",
+            Function::CountNonEmpty => "count-non-empty",
+            Function::Sum => "sum",
+            Function::Mean => "mean",
+            Function::Min => "min",
+            Function::Max => "max",
+            Function::CountDistinct => "count-distinct",
+            Function::JoinDistinct => "join-distinct",
+        }
+    }
+}
+
+impl Group {
+    pub fn new(by: Vec<ColumnRef>, aggregates: Vec<Aggregate>) -> Self {
+        Group { by, aggregates }
+    }
+
+    pub fn by(&self) -> &[ColumnRef] {
+        &self.by
+    }
+
+    pub fn aggregates(&self) -> &[Aggregate] {
+        &self.aggregates
+    }
+
+    /// Headers of the grouped records
+    pub fn headers(&self) -> Vec<&str> {
+        let by = self.by.iter().map(|column| column.header.as_str());
+        by.chain(self.aggregates.iter().map(|a| a.header.as_str()))
+            .collect()
+    }
+
+    /// Types of the grouped records, from the declared types of the columns they read
+    pub fn column_types(&self, types: &[Option<ValueType>]) -> Vec<Option<ValueType>> {
+        let by = self.by.iter().map(|column| types[column.index]);
+        by.chain(self.aggregates.iter().map(|a| match a.function {
+            Function::Count | Function::CountNonEmpty | Function::CountDistinct => {
+                Some(ValueType::Integer)
+            }
+            Function::Mean => Some(ValueType::Float),
+            Function::Min | Function::Max => a.column.as_ref().and_then(|c| types[c.index]),
+            Function::Sum | Function::JoinDistinct => None,
+        }))
+        .collect()
+    }
+
+    /// Check all the column references against the headers of the output of the rules
+    pub fn validate(&self, headers: &[String]) -> Result<()> {
+        let columns = self.aggregates.iter().filter_map(|a| a.column.as_ref());
+        for column in self.by.iter().chain(columns) {
+            let is_valid = headers
+                .get(column.index)
+                .map(|header| header == &column.header)
+                .unwrap_or(false);
+            ensure!(is_valid, "invalid column reference in group: {}", column);
+        }
+        Ok(())
+    }
+
+    /// Start aggregating records
+    pub fn aggregator(&self) -> Aggregator<'_> {
+        Aggregator {
+            group: self,
+            groups: HashMap::new(),
+            order: vec![],
+        }
+    }
+}
+
+impl Aggregate {
+    pub fn new(header: String, function: Function, column: Option<ColumnRef>) -> Result<Self> {
+        let params = AggregateParams {
+            header,
+            function,
+            column,
+            sep: None,
+        };
+        Aggregate::try_from(params).map_err(anyhow::Error::msg)
+    }
+
+    /// Set the separator of values for `join-distinct`, `", "` by default
+    pub fn with_sep(mut self, sep: String) -> Self {
+        self.sep = Some(sep);
+        self
+    }
+
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    pub fn function(&self) -> Function {
+        self.function
+    }
+
+    pub fn column(&self) -> Option<&ColumnRef> {
+        self.column.as_ref()
+    }
+}
+
+impl TryFrom<AggregateParams> for Aggregate {
+    type Error = String;
+
+    fn try_from(params: AggregateParams) -> Result<Self, Self::Error> {
+        let AggregateParams {
+            header,
+            function,
+            column,
+            sep,
+        } = params;
+        match (function, &column) {
+            (Function::Count, Some(_)) => {
+                return Err(format!(
+                    "aggregate `{}`: count doesn't take a column",
+                    header
+                ));
+            }
+            (Function::Count, None) | (_, Some(_)) => {}
+            (_, None) => return Err(format!("aggregate `{}`: missing column", header)),
+        }
+        if sep.is_some() && function != Function::JoinDistinct {
+            return Err(format!(
+                "aggregate `{}`: sep can only be used with join-distinct",
+                header,
+            ));
+        }
+        Ok(Aggregate {
+            header,
+            function,
+            column,
+            sep,
+        })
+    }
+}
+
+/// Aggregates of the records added so far in each group
+pub struct Aggregator<'a> {
+    group: &'a Group,
+    /// Values of the grouping columns and states of the aggregates for each group
+    groups: HashMap<Vec<String>, (Vec<Value>, Vec<State>)>,
+    /// Keys of the groups in the order they first appear
+    order: Vec<Vec<String>>,
+}
+
+/// State of an aggregate in a group
+#[derive(Default)]
+struct State {
+    count: usize,
+    sum: f64,
+    /// Exact sum of the values while all of them are integers and it doesn't overflow
+    integer_sum: Option<i64>,
+    /// Minimum or maximum value compared as numbers and as text
+    numeric: Option<(f64, Value)>,
+    text: Option<(String, Value)>,
+    /// Whether all the values are numbers
+    all_numeric: bool,
+    distinct: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl<'a> Aggregator<'a> {
+    /// Add an output record of the rules into its group
+    pub fn add(&mut self, record: &[Value]) -> Result<()> {
+        let by = self.group.by.iter();
+        let key: Vec<_> = by.clone().map(|c| record[c.index].to_string()).collect();
+        let aggregates = &self.group.aggregates;
+        let order = &mut self.order;
+        let (_, states) = self.groups.entry(key).or_insert_with_key(|key| {
+            order.push(key.clone());
+            let values = by.map(|c| record[c.index].clone()).collect();
+            let states = aggregates.iter().map(|_| State::new()).collect();
+            (values, states)
+        });
+        for (aggregate, state) in aggregates.iter().zip(states.iter_mut()) {
+            let value = aggregate.column.as_ref().map(|c| &record[c.index]);
+            state
+                .add(aggregate.function, value)
+                .with_context(|| format!("aggregate `{}`", aggregate.header))?;
+        }
+        Ok(())
+    }
+
+    /// Records of all the groups in the order they first appear
+    pub fn finish(mut self) -> Vec<Vec<Value>> {
+        let aggregates = &self.group.aggregates;
+        let groups = &mut self.groups;
+        let records = self.order.iter().map(|key| {
+            let (mut values, states) = groups.remove(key).unwrap();
+            for (aggregate, state) in aggregates.iter().zip(states) {
+                let sep = aggregate.sep.as_deref().unwrap_or(", ");
+                values.push(state.finish(aggregate.function, sep));
+            }
+            values
+        });
+        records.collect()
+    }
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            integer_sum: Some(0),
+            all_numeric: true,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, function: Function, value: Option<&Value>) -> Result<()> {
+        let value = match value {
+            None => {
+                self.count += 1;
+                return Ok(());
+            }
+            Some(Value::Null) => return Ok(()),
+            Some(value) => value,
+        };
+        let text = value.to_string();
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        self.count += 1;
+        // Infinity and NaN are not treated as numbers, which can't be summed or compared
+        let number = text.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+        match function {
+            Function::Count | Function::CountNonEmpty => {}
+            Function::Sum | Function::Mean => {
+                let number = number.with_context(|| format!("`{}` is not a valid number", text))?;
+                self.sum += number;
+                ensure!(self.sum.is_finite(), "sum is out of range at `{}`", text);
+                let integer = text.trim().parse::<i64>().ok();
+                self.integer_sum = match (self.integer_sum, integer) {
+                    (Some(sum), Some(integer)) => sum.checked_add(integer),
+                    _ => None,
+                };
+            }
+            Function::Min | Function::Max => {
+                let ordering = |a: &f64, b: &f64| match function {
+                    Function::Min => a < b,
+                    _ => a > b,
+                };
+                match number {
+                    Some(number) => match &self.numeric {
+                        Some((best, _)) if !ordering(&number, best) => {}
+                        _ => self.numeric = Some((number, value.clone())),
+                    },
+                    None => self.all_numeric = false,
+                }
+                let better = match &self.text {
+                    Some((best, _)) => match function {
+                        Function::Min => text < *best,
+                        _ => text > *best,
+                    },
+                    None => true,
+                };
+                if better {
+                    self.text = Some((text, value.clone()));
+                }
+            }
+            Function::CountDistinct | Function::JoinDistinct => {
+                if self.seen.insert(text.clone()) {
+                    self.distinct.push(text);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, function: Function, sep: &str) -> Value {
+        match function {
+            Function::Count | Function::CountNonEmpty => Value::Integer(self.count as i64),
+            // Without any value there is no sum, the same as the mean, min and max
+            Function::Sum if self.count == 0 => Value::Null,
+            Function::Sum => match self.integer_sum {
+                Some(sum) => Value::Integer(sum),
+                None => Value::Float(self.sum),
+            },
+            Function::Mean if self.count == 0 => Value::Null,
+            Function::Mean => {
+                let sum = self.integer_sum.map_or(self.sum, |sum| sum as f64);
+                Value::Float(sum / self.count as f64)
+            }
+            Function::Min | Function::Max => {
+                let best = if self.all_numeric {
+                    self.numeric.map(|(_, value)| value)
+                } else {
+                    self.text.map(|(_, value)| value)
+                };
+                best.unwrap_or(Value::Null)
+            }
+            Function::CountDistinct => Value::Integer(self.distinct.len() as i64),
+            Function::JoinDistinct => Value::Text(self.distinct.join(sep)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_groups() {
+        let column = |index, header: &str| ColumnRef {
+            index,
+            header: header.to_string(),
+        };
+        let aggregate = |header: &str, function, index: Option<usize>| {
+            let column = index.map(|index| column(index, "Value"));
+            Aggregate::new(header.to_string(), function, column).unwrap()
+        };
+        let group = Group::new(
+            vec![column(0, "Country")],
+            vec![
+                aggregate("Count", Function::Count, None),
+                aggregate("Answered", Function::CountNonEmpty, Some(1)),
+                aggregate("Sum", Function::Sum, Some(1)),
+                aggregate("Mean", Function::Mean, Some(1)),
+                aggregate("Min", Function::Min, Some(1)),
+                aggregate("Max", Function::Max, Some(1)),
+                aggregate("Distinct", Function::CountDistinct, Some(1)),
+                aggregate("Values", Function::JoinDistinct, Some(1)),
+            ],
+        );
+        let mut aggregator = group.aggregator();
+        let records = [
+            ["China", "9"],
+            ["Japan", "2.5"],
+            ["China", ""],
+            ["China", "10"],
+            ["India", "9007199254740993"],
+            ["India", "1"],
+            ["Korea", ""],
+        ];
+        for record in records.iter() {
+            let record: Vec<Value> = record.iter().map(|v| Value::from(*v)).collect();
+            aggregator.add(&record).unwrap();
+        }
+        let records = aggregator.finish();
+        let records = records
+            .iter()
+            .map(|record| record.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            [
+                ["China", "3", "2", "19", "9.5", "9", "10", "2", "9, 10"],
+                ["Japan", "1", "1", "2.5", "2.5", "2.5", "2.5", "1", "2.5"],
+                [
+                    "India",
+                    "2",
+                    "2",
+                    "9007199254740994",
+                    "4503599627370497",
+                    "1",
+                    "9007199254740993",
+                    "2",
+                    "9007199254740993, 1",
+                ],
+                ["Korea", "1", "0", "", "", "", "", "0", ""],
+            ],
+        );
+
+        let mut aggregator = group.aggregator();
+        let error = aggregator
+            .add(&["China".into(), "N/A".into()])
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "aggregate `Sum`: `N/A` is not a valid number",
+        );
+        let error = aggregator.add(&["China".into(), "inf".into()]).err();
+        assert_eq!(
+            format!("{:#}", error.unwrap()),
+            "aggregate `Sum`: `inf` is not a valid number",
+        );
+
+        // Non-finite values are compared as text along with the others
+        let group = Group::new(
+            vec![column(0, "Country")],
+            vec![
+                aggregate("Min", Function::Min, Some(1)),
+                aggregate("Max", Function::Max, Some(1)),
+            ],
+        );
+        let mut aggregator = group.aggregator();
+        for value in ["NaN", "10", "9"].iter() {
+            aggregator.add(&["China".into(), (*value).into()]).unwrap();
+        }
+        let records = aggregator.finish();
+        assert_eq!(records, [["China".into(), "10".into(), "NaN".into()]]);
+        assert!(
+            Aggregate::new("Count".to_string(), Function::Count, Some(column(1, "V"))).is_err()
+        );
+    }
+}
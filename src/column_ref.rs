@@ -1,6 +1,5 @@
 use serde::de;
 use serde::de::Unexpected;
-use serde::export::Formatter;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::str;
@@ -27,7 +26,7 @@ impl FromStr for ColumnRef {
 }
 
 impl fmt::Display for ColumnRef {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut buf = [0; 16];
         write!(f, "{}: {}", index_to_ref(self.index, &mut buf), self.header)
     }
@@ -90,7 +89,7 @@ fn ref_to_index(s: &str) -> Option<usize> {
         _ => return None,
     };
     for b in iter {
-        if !(b'A'..=b'Z').contains(&b) {
+        if !b.is_ascii_uppercase() {
             return None;
         }
         index = (index + 1) * 26;
@@ -100,7 +99,7 @@ fn ref_to_index(s: &str) -> Option<usize> {
 }
 
 fn is_ref_byte(b: u8) -> bool {
-    (b'A'..=b'Z').contains(&b)
+    b.is_ascii_uppercase()
 }
 
 #[cfg(test)]
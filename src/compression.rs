@@ -0,0 +1,112 @@
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Compression of a file, detected from its extension
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    /// `.gz`
+    Gzip,
+    /// `.zst`
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression from the extension of the file,
+    /// and return it along with the extension of the content in lowercase,
+    /// e.g. `csv` for `result.csv.gz`
+    pub fn detect(path: &Path) -> (Self, Option<String>) {
+        let extension = |path: &Path| {
+            let extension = path.extension().and_then(OsStr::to_str);
+            extension.map(str::to_ascii_lowercase)
+        };
+        let compression = match extension(path).as_deref() {
+            Some("gz") | Some("gzip") => Compression::Gzip,
+            Some("zst") | Some("zstd") => Compression::Zstd,
+            _ => return (Compression::None, extension(path)),
+        };
+        let stem = path.file_stem().map(Path::new);
+        (compression, stem.and_then(extension))
+    }
+
+    /// Wrap the reader to decompress the content
+    pub fn decoder<'a>(self, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+
+    /// Wrap the writer to compress the content
+    pub fn encoder<W: Write>(self, writer: W) -> Result<Encoder<W>> {
+        Ok(match self {
+            Compression::None => Encoder::None(writer),
+            Compression::Gzip => Encoder::Gzip(GzEncoder::new(writer, Default::default())),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, 0)?),
+        })
+    }
+}
+
+/// Writer compressing the content, which needs to be finished explicitly
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write the remaining compressed content, and return the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::None(writer) => Ok(writer),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let path = Path::new("result.CSV.gz");
+        assert!(Compression::detect(path) == (Compression::Gzip, Some("csv".to_string())));
+        let path = Path::new("result.xlsx");
+        assert!(Compression::detect(path) == (Compression::None, Some("xlsx".to_string())));
+
+        for compression in [Compression::Gzip, Compression::Zstd].iter() {
+            let mut encoder = compression.encoder(vec![]).unwrap();
+            encoder.write_all(b"a,b\n1,2\n").unwrap();
+            let compressed = encoder.finish().unwrap();
+            let mut decoded = String::new();
+            let mut decoder = compression.decoder(compressed.as_slice()).unwrap();
+            decoder.read_to_string(&mut decoded).unwrap();
+            assert_eq!(decoded, "a,b\n1,2\n");
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use calamine::{open_workbook_auto_from_rs, Data, Reader};
+use chrono::NaiveTime;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
+
+/// Data in a table with headers
+pub struct CsvData {
+    pub headers: Vec<String>,
+    pub values: Vec<Vec<String>>,
+}
+
+impl CsvData {
+    pub fn from_reader<R: Read>(reader: &mut csv::Reader<R>) -> Result<Self> {
+        let headers = reader
+            .headers()
+            .context("read headers")?
+            .iter()
+            .map(|header| header.to_string())
+            .collect();
+        let values = reader
+            .records()
+            .enumerate()
+            .map(|(i, record)| {
+                let record = record.with_context(|| format!("read record {}", i))?;
+                Ok(record.iter().map(|r| r.to_string()).collect())
+            })
+            .collect::<Result<_>>()?;
+        Ok(CsvData { headers, values })
+    }
+
+    /// Read the data from a JSON array of objects
+    ///
+    /// The headers are the union of keys of all the objects in the order they first appear.
+    /// If `flatten` is set, nested objects are flattened into columns with dotted paths.
+    pub fn from_json(reader: impl Read, flatten: bool) -> Result<Self> {
+        let objects: Vec<Map<String, Value>> =
+            serde_json::from_reader(reader).context("parse json")?;
+        Ok(Self::from_objects(objects, flatten))
+    }
+
+    /// Read the data from newline-delimited JSON objects, see [`CsvData::from_json`]
+    pub fn from_ndjson(reader: impl BufRead, flatten: bool) -> Result<Self> {
+        let objects = reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|(i, line)| {
+                let line = line.with_context(|| format!("read line {}", i + 1))?;
+                serde_json::from_str(&line).with_context(|| format!("parse line {}", i + 1))
+            })
+            .collect::<Result<Vec<Map<String, Value>>>>()?;
+        Ok(Self::from_objects(objects, flatten))
+    }
+
+    /// Read the data from a sheet in a `.xlsx`, `.xls` or `.ods` workbook,
+    /// where the first row contains the headers
+    ///
+    /// The kind of the workbook is detected from the content, which may clone the reader
+    /// several times, so it should be cheap to clone, e.g. `Cursor<&[u8]>`.
+    /// The sheet can be given by its name or its index, and the first sheet is used by default.
+    pub fn from_workbook<RS>(reader: RS, sheet: Option<&str>) -> Result<Self>
+    where
+        RS: Read + Seek + Clone,
+    {
+        let mut workbook = open_workbook_auto_from_rs(reader).context("open workbook")?;
+        let names = workbook.sheet_names();
+        let name = match sheet {
+            None => names.first(),
+            Some(sheet) => names.iter().find(|name| *name == sheet).or_else(|| {
+                let index = sheet.parse::<usize>().ok()?;
+                names.get(index)
+            }),
+        };
+        let name = name
+            .ok_or_else(|| {
+                let sheet = sheet.unwrap_or_default();
+                anyhow!(
+                    "sheet `{}` not found, expected one of: {}",
+                    sheet,
+                    names.join(", ")
+                )
+            })?
+            .clone();
+        let range = workbook
+            .worksheet_range(&name)
+            .with_context(|| format!("read sheet {}", name))?;
+        let mut rows = range.rows().map(|row| row.iter().map(cell_text).collect());
+        let headers = rows.next().unwrap_or_default();
+        let values = rows.collect();
+        Ok(CsvData { headers, values })
+    }
+
+    /// Append the records of another data with the same headers
+    ///
+    /// If `rebase` is set, the other data can have its columns in a different order,
+    /// which are matched by the header text, and columns missing from it are left empty.
+    /// The indices of the missing columns are returned.
+    pub fn append(&mut self, other: CsvData, rebase: bool) -> Result<Vec<usize>> {
+        if other.headers == self.headers {
+            self.values.extend(other.values);
+            return Ok(vec![]);
+        }
+        ensure!(
+            rebase,
+            "headers differ from the first file, expected: {}",
+            self.headers.join(", "),
+        );
+        // Index of the column in the other data for each column,
+        // where duplicate headers are matched in their order
+        let mut used = vec![false; other.headers.len()];
+        let mut indices = vec![];
+        for header in self.headers.iter() {
+            let found =
+                (other.headers.iter().zip(used.iter())).position(|(h, used)| h == header && !used);
+            if let Some(i) = found {
+                used[i] = true;
+            }
+            indices.push(found);
+        }
+        if let Some(i) = used.iter().position(|used| !used) {
+            bail!("column `{}` isn't in the first file", other.headers[i]);
+        }
+        self.values.extend(other.values.into_iter().map(|record| {
+            let fields = indices.iter();
+            let fields = fields.map(|i| i.map(|i| record[i].clone()).unwrap_or_default());
+            fields.collect()
+        }));
+        let missing = indices.iter().enumerate().filter(|(_, i)| i.is_none());
+        Ok(missing.map(|(col, _)| col).collect())
+    }
+
+    fn from_objects(objects: Vec<Map<String, Value>>, flatten: bool) -> Self {
+        let mut headers = vec![];
+        let mut indices = HashMap::new();
+        let mut values = vec![];
+        for object in objects {
+            let mut fields = vec![];
+            flatten_object(object, "", flatten, &mut fields);
+            let mut record = vec![String::new(); headers.len()];
+            for (key, value) in fields {
+                let index = *indices.entry(key.clone()).or_insert_with(|| {
+                    headers.push(key);
+                    headers.len() - 1
+                });
+                if index >= record.len() {
+                    record.resize(index + 1, String::new());
+                }
+                record[index] = value;
+            }
+            values.push(record);
+        }
+        // Fill in keys missing from objects before they first appear
+        for record in values.iter_mut() {
+            record.resize(headers.len(), String::new());
+        }
+        CsvData { headers, values }
+    }
+}
+
+/// Text of a cell in a workbook, where dates are written as `%Y-%m-%d`,
+/// timestamps as `%Y-%m-%d %H:%M:%S`, and durations as hours, minutes and seconds
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(datetime) if datetime.is_duration() => {
+            let seconds = datetime.as_duration().unwrap().num_seconds();
+            let sign = if seconds < 0 { "-" } else { "" };
+            let seconds = seconds.abs();
+            let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+            format!("{}{}:{:02}:{:02}", sign, hours, minutes, seconds % 60)
+        }
+        Data::DateTime(datetime) => match datetime.as_datetime() {
+            Some(datetime) if datetime.time() == NaiveTime::MIN => {
+                datetime.format("%Y-%m-%d").to_string()
+            }
+            Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => cell.to_string(),
+        },
+        _ => cell.to_string(),
+    }
+}
+
+fn flatten_object(
+    object: Map<String, Value>,
+    prefix: &str,
+    flatten: bool,
+    fields: &mut Vec<(String, String)>,
+) {
+    for (key, value) in object {
+        let key = format!("{}{}", prefix, key);
+        let value = match value {
+            Value::Object(object) if flatten => {
+                flatten_object(object, &format!("{}.", key), flatten, fields);
+                continue;
+            }
+            Value::Null => String::new(),
+            Value::String(s) => s,
+            Value::Bool(_) | Value::Number(_) | Value::Array(_) | Value::Object(_) => {
+                value.to_string()
+            }
+        };
+        fields.push((key, value));
+    }
+}
+
+pub fn parse_csv(path: &Path) -> Result<CsvData> {
+    let mut reader = csv::Reader::from_path(path).context("open csv file")?;
+    CsvData::from_reader(&mut reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_json_objects() {
+        let input = r#"
+        {"id": 1, "answers": {"lang": "Rust", "years": 3}, "tags": ["a"]}
+
+        {"id": 2, "extra": null, "answers": {"lang": "C++"}}
+        "#;
+        let data = CsvData::from_ndjson(input.trim().as_bytes(), true).unwrap();
+        assert_eq!(
+            data.headers,
+            ["id", "answers.lang", "answers.years", "tags", "extra"],
+        );
+        assert_eq!(
+            data.values,
+            [["1", "Rust", "3", r#"["a"]"#, ""], ["2", "C++", "", "", ""],],
+        );
+
+        let input = r#"[{"id": 1, "answers": {"lang": "Rust"}}]"#;
+        let data = CsvData::from_json(input.as_bytes(), false).unwrap();
+        assert_eq!(data.headers, ["id", "answers"]);
+        assert_eq!(data.values, [["1", r#"{"lang":"Rust"}"#]]);
+    }
+
+    #[test]
+    fn workbook_cell_text() {
+        use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+        let cell = |value, kind, is_1904| Data::DateTime(ExcelDateTime::new(value, kind, is_1904));
+        let date = ExcelDateTimeType::DateTime;
+        assert_eq!(cell_text(&cell(44100.0, date, false)), "2020-09-26");
+        assert_eq!(cell_text(&cell(42638.0, date, true)), "2020-09-26");
+        assert_eq!(
+            cell_text(&cell(44100.5, date, false)),
+            "2020-09-26 12:00:00"
+        );
+        let duration = ExcelDateTimeType::TimeDelta;
+        assert_eq!(cell_text(&cell(1.5, duration, false)), "36:00:00");
+        assert_eq!(cell_text(&Data::Float(2.5)), "2.5");
+    }
+
+    #[test]
+    fn append_data() {
+        let data = |headers: &[&str], values: &[&[&str]]| CsvData {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            values: values
+                .iter()
+                .map(|record| record.iter().map(|v| v.to_string()).collect())
+                .collect(),
+        };
+        let mut base = data(&["Q", "A", "Q"], &[&["1", "2", "3"]]);
+        base.append(data(&["Q", "A", "Q"], &[&["4", "5", "6"]]), false)
+            .unwrap();
+        let other = || data(&["A", "Q"], &[&["8", "7"]]);
+        assert!(base.append(other(), false).is_err());
+        assert_eq!(base.append(other(), true).unwrap(), [2]);
+        assert_eq!(
+            base.values,
+            [["1", "2", "3"], ["4", "5", "6"], ["7", "8", ""]]
+        );
+
+        let error = base.append(data(&["A", "B"], &[]), true).err().unwrap();
+        assert_eq!(error.to_string(), "column `B` isn't in the first file");
+    }
+}
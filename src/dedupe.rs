@@ -0,0 +1,181 @@
+use crate::output::Destination;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use csv_transformer::{ColumnRef, CsvData};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+// Arguments for removing duplicate records from the original data
+#[derive(StructOpt)]
+pub struct Dedupe {
+    /// Remove records with the same values in the given columns of the original data,
+    /// can be given multiple times to use multiple columns as the key
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_column_ref))]
+    dedupe: Vec<ColumnRef>,
+    /// Which of the duplicate records to keep, `first`, `last` or `most-complete`,
+    /// which is the one with the most non-empty values
+    #[structopt(long, default_value = "first")]
+    keep: Keep,
+    /// Write the removed duplicate records into the given CSV file
+    #[structopt(long, parse(from_os_str))]
+    duplicates: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Keep {
+    First,
+    Last,
+    MostComplete,
+}
+
+impl FromStr for Keep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "first" => Ok(Keep::First),
+            "last" => Ok(Keep::Last),
+            "most-complete" => Ok(Keep::MostComplete),
+            _ => bail!("unknown record to keep: {}", s),
+        }
+    }
+}
+
+fn parse_column_ref(s: &str) -> Result<ColumnRef> {
+    ColumnRef::from_str(s).map_err(|_| anyhow!("invalid column reference: {}", s))
+}
+
+impl Dedupe {
+    /// Whether any key column is given
+    pub fn is_enabled(&self) -> bool {
+        !self.dedupe.is_empty()
+    }
+
+    /// Key columns in the original data
+    pub fn columns(&self) -> &[ColumnRef] {
+        &self.dedupe
+    }
+
+    /// Path to write the removed duplicate records into
+    pub fn duplicates(&self) -> Option<&Path> {
+        self.duplicates.as_deref()
+    }
+
+    /// Remove the duplicate records from the data, and return the removed ones
+    pub fn apply(&self, data: &mut CsvData) -> Result<Vec<Vec<String>>> {
+        if !self.is_enabled() {
+            ensure!(
+                self.duplicates.is_none(),
+                "--duplicates can only be used with --dedupe",
+            );
+            return Ok(vec![]);
+        }
+        for column in self.dedupe.iter() {
+            let is_valid = data.headers.get(column.index) == Some(&column.header);
+            ensure!(is_valid, "invalid column reference: {}", column);
+        }
+        let keys: Vec<_> = self.dedupe.iter().map(|column| column.index).collect();
+        let values = std::mem::take(&mut data.values);
+        let (kept, removed) = dedupe(values, &keys, self.keep);
+        data.values = kept;
+        Ok(removed)
+    }
+
+    /// Write the removed duplicate records into the file given via `--duplicates`, if any
+    pub fn write_duplicates(&self, headers: &[String], removed: &[Vec<String>]) -> Result<()> {
+        let path = match &self.duplicates {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let destination = Destination::create(Some(path))?;
+        let mut writer = csv::Writer::from_writer(destination.clone());
+        writer
+            .write_record(headers)
+            .context("write duplicates header")?;
+        for record in removed.iter() {
+            writer
+                .write_record(record)
+                .context("write duplicate record")?;
+        }
+        writer.flush().context("write duplicates")?;
+        destination.commit()
+    }
+}
+
+/// Split the records into the kept ones and the removed duplicates, both in their original order
+///
+/// Records with all the key columns empty are never considered duplicates.
+fn dedupe(
+    records: Vec<Vec<String>>,
+    keys: &[usize],
+    keep: Keep,
+) -> (Vec<Vec<String>>, Vec<Vec<String>>) {
+    let completeness = |record: &[String]| record.iter().filter(|v| !v.trim().is_empty()).count();
+    // Index of the record to keep for each key
+    let mut chosen = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        let key: Vec<_> = keys.iter().map(|k| record[*k].as_str()).collect();
+        if key.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let current = chosen.entry(key).or_insert(i);
+        let replace = match keep {
+            Keep::First => false,
+            Keep::Last => true,
+            Keep::MostComplete => completeness(record) > completeness(&records[*current]),
+        };
+        if replace {
+            *current = i;
+        }
+    }
+
+    let mut is_kept = vec![true; records.len()];
+    for (i, record) in records.iter().enumerate() {
+        let key: Vec<_> = keys.iter().map(|k| record[*k].as_str()).collect();
+        if let Some(kept) = chosen.get(&key) {
+            is_kept[i] = *kept == i;
+        }
+    }
+    let (mut kept, mut removed) = (vec![], vec![]);
+    for (record, is_kept) in records.into_iter().zip(is_kept) {
+        if is_kept {
+            kept.push(record);
+        } else {
+            removed.push(record);
+        }
+    }
+    (kept, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn dedupe_records() {
+        let records = [
+            ["a@example.com", "1", ""],
+            ["b@example.com", "2", "x"],
+            ["a@example.com", "3", "y"],
+            ["", "4", ""],
+            ["a@example.com", "5", ""],
+            ["", "6", ""],
+        ];
+        let records: Vec<Vec<String>> = records
+            .iter()
+            .map(|r| r.iter().map(|v| v.to_string()).collect())
+            .collect();
+        let column = |records: &[Vec<String>]| records.iter().map(|r| r[1].clone()).collect_vec();
+        let (kept, removed) = dedupe(records.clone(), &[0], Keep::First);
+        assert_eq!(column(&kept), ["1", "2", "4", "6"]);
+        assert_eq!(column(&removed), ["3", "5"]);
+        let (kept, removed) = dedupe(records.clone(), &[0], Keep::Last);
+        assert_eq!(column(&kept), ["2", "4", "5", "6"]);
+        assert_eq!(column(&removed), ["1", "3"]);
+        let (kept, removed) = dedupe(records, &[0], Keep::MostComplete);
+        assert_eq!(column(&kept), ["2", "3", "4", "6"]);
+        assert_eq!(column(&removed), ["1", "5"]);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::compression::Compression;
+use anyhow::{bail, ensure, Context, Result};
+use csv_transformer::{ColumnRef, CsvData};
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+// Arguments for reading the original data, shared by all the commands
+#[derive(StructOpt)]
+pub struct Input {
+    /// CSV file, JSON / NDJSON file with extension `.json`, `.ndjson` or `.jsonl`,
+    /// or workbook with extension `.xlsx`, `.xls` or `.ods`, optionally compressed with
+    /// extension `.gz` or `.zst`, or `-` for stdin.
+    /// Multiple files with the same headers are stacked into one
+    #[structopt(parse(from_os_str), required = true)]
+    pub original: Vec<PathBuf>,
+    /// Match columns of the files after the first one by header text,
+    /// instead of requiring their headers to be the same
+    #[structopt(long)]
+    pub rebase: bool,
+    #[structopt(flatten)]
+    pub options: ReadOptions,
+}
+
+// Arguments for reading each original file
+#[derive(StructOpt)]
+pub struct ReadOptions {
+    /// Format of the original data, `csv`, `json`, `ndjson`, `xlsx` or `ods`,
+    /// instead of the one from the file extension
+    #[structopt(long)]
+    pub input_format: Option<InputFormat>,
+    /// Flatten nested objects in JSON input into columns with dotted paths
+    #[structopt(long)]
+    pub flatten: bool,
+    /// Name or index of the sheet to read from a workbook, the first sheet by default
+    #[structopt(long)]
+    pub sheet: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+pub enum InputFormat {
+    Csv,
+    Json,
+    Ndjson,
+    /// Any kind of workbook, `.xlsx`, `.xls` or `.ods`
+    Workbook,
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "json" => Ok(InputFormat::Json),
+            "ndjson" | "jsonl" => Ok(InputFormat::Ndjson),
+            "xlsx" | "xls" | "ods" => Ok(InputFormat::Workbook),
+            _ => bail!("unknown format: {}", s),
+        }
+    }
+}
+
+/// Columns of the first file missing from each of the other files,
+/// which are left empty when the files are stacked with `--rebase`
+pub struct MissingColumns(Vec<(PathBuf, Vec<usize>)>);
+
+impl Input {
+    /// Read the original data from all the files stacked into one
+    pub fn read(&self) -> Result<CsvData> {
+        Ok(self.read_with_missing()?.0)
+    }
+
+    /// Read the original data from all the files stacked into one,
+    /// along with the columns missing from each file
+    pub fn read_with_missing(&self) -> Result<(CsvData, MissingColumns)> {
+        let mut paths = self.original.iter();
+        let path = paths.next().unwrap();
+        let mut data = self
+            .options
+            .read_file(path)
+            .with_context(|| format!("read {}", path.display()))?;
+        let mut missing = vec![];
+        for path in paths {
+            let other = self.options.read_file(path);
+            let other = other.and_then(|other| data.append(other, self.rebase));
+            let columns = other.with_context(|| format!("read {}", path.display()))?;
+            if !columns.is_empty() {
+                missing.push((path.clone(), columns));
+            }
+        }
+        Ok((data, MissingColumns(missing)))
+    }
+}
+
+impl MissingColumns {
+    /// Ensure that none of the given columns is missing from any file,
+    /// so that they are never read as empty values instead
+    pub fn check<'a>(&self, columns: impl IntoIterator<Item = &'a ColumnRef>) -> Result<()> {
+        for column in columns {
+            for (path, missing) in self.0.iter() {
+                ensure!(
+                    !missing.contains(&column.index),
+                    "column `{}` is missing from {}",
+                    column,
+                    path.display(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ReadOptions {
+    /// Read the file in the given format or the one from the extension of the file,
+    /// decompressing it if the extension is `.gz` or `.zst`
+    pub fn read_file(&self, path: &Path) -> Result<CsvData> {
+        let is_stdin = path == Path::new("-");
+        let (compression, extension) = if is_stdin {
+            (Compression::None, None)
+        } else {
+            Compression::detect(path)
+        };
+        let format = self.input_format.unwrap_or_else(|| {
+            let format = extension.and_then(|extension| extension.parse().ok());
+            format.unwrap_or(InputFormat::Csv)
+        });
+        if self.sheet.is_some() && !matches!(format, InputFormat::Workbook) {
+            bail!("--sheet can only be used with a workbook");
+        }
+        let reader: Box<dyn Read> = if is_stdin {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(path).context("open original file")?)
+        };
+        let reader = BufReader::new(compression.decoder(reader)?);
+        match format {
+            InputFormat::Csv => CsvData::from_reader(&mut csv::Reader::from_reader(reader)),
+            InputFormat::Json => CsvData::from_json(reader, self.flatten),
+            InputFormat::Ndjson => CsvData::from_ndjson(reader, self.flatten),
+            InputFormat::Workbook => {
+                let mut reader = reader;
+                let mut content = vec![];
+                reader.read_to_end(&mut content).context("read workbook")?;
+                CsvData::from_workbook(Cursor::new(&content[..]), self.sheet.as_deref())
+            }
+        }
+        .context("parse original file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv_transformer::Transformation;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn read_missing_columns() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("csv-transformer-{}.{}.csv", name, process::id()));
+        let paths = vec![path("rebase-1"), path("rebase-2")];
+        fs::write(&paths[0], "Name,Age,Country\nAlice,30,China\n").unwrap();
+        fs::write(&paths[1], "Country,Name\nSpain,Bob\n").unwrap();
+        let input = Input {
+            original: paths.clone(),
+            rebase: true,
+            options: ReadOptions {
+                input_format: None,
+                flatten: false,
+                sheet: None,
+            },
+        };
+        let result = input.read_with_missing();
+        for path in paths.iter() {
+            fs::remove_file(path).unwrap();
+        }
+        let (data, missing) = result.unwrap();
+        assert_eq!(data.values[1], ["Bob", "", "Spain"]);
+
+        let check = |yaml: &str| {
+            let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+            missing.check(transformation.source_columns())
+        };
+        check("- \"A: Name\"\n- \"C: Country\"\n").unwrap();
+        let error = check("- \"C: Country\"\n- \"B: Age\"\n").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("column `B: Age` is missing from {}", paths[1].display()),
+        );
+    }
+}
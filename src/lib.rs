@@ -0,0 +1,22 @@
+//! Library to rearrange CSV files in certain ways.
+//!
+//! A [`Transformation`] can be loaded from a YAML document in the same format as
+//! the transformation file of the command line tool, or built from the rules in
+//! [`transform`] programmatically.
+
+pub use crate::aggregate::{Aggregate, Aggregator, Function, Group};
+pub use crate::column_ref::ColumnRef;
+pub use crate::data::{parse_csv, CsvData};
+pub use crate::sort::{Comparison, Order, SortColumn, SortKey};
+pub use crate::stage::Stage;
+pub use crate::transformation::Transformation;
+pub use crate::value::{Value, ValueType};
+
+mod aggregate;
+pub mod column_ref;
+mod data;
+mod sort;
+mod stage;
+pub mod transform;
+mod transformation;
+mod value;
@@ -0,0 +1,181 @@
+use crate::output::Destination;
+use anyhow::{bail, Context, Result};
+use csv_transformer::transform::Transform;
+use csv_transformer::{ColumnRef, Transformation};
+use itertools::Itertools;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// Where an output column comes from
+#[derive(Serialize)]
+pub struct Lineage<'a> {
+    /// Reference to the column in the output
+    column: ColumnRef,
+    /// Index of the rule producing the column in the last stage,
+    /// or the index of the aggregate producing it if grouped
+    rule: usize,
+    /// Kind of the transformation of the rule, or the function of the aggregate
+    transform: &'a str,
+    /// Columns in the original data the column is generated from
+    sources: Vec<&'a ColumnRef>,
+}
+
+/// Find the columns in the original data each output column comes from,
+/// tracing through all the stages of the transformation and the grouping
+pub fn lineage(transformation: &Transformation) -> Vec<Lineage<'_>> {
+    let mut result: Vec<Lineage> = vec![];
+    for (i, stage) in transformation.stages().iter().enumerate() {
+        let input = result;
+        result = vec![];
+        for (rule, col) in stage.rules().iter().enumerate() {
+            let sources = if i == 0 {
+                col.columns()
+            } else {
+                let columns = col.columns().into_iter();
+                let sources = columns.flat_map(|c| input[c.index].sources.iter().copied());
+                sources.unique_by(|c| c.index).collect()
+            };
+            for header in col.headers() {
+                result.push(Lineage {
+                    column: ColumnRef {
+                        index: result.len(),
+                        header: header.to_string(),
+                    },
+                    rule,
+                    transform: col.kind(),
+                    sources: sources.clone(),
+                });
+            }
+        }
+    }
+    if let Some(group) = transformation.group() {
+        let input = result;
+        result = vec![];
+        for column in group.by() {
+            let Lineage {
+                rule, transform, ..
+            } = input[column.index];
+            let sources = input[column.index].sources.clone();
+            result.push(Lineage {
+                column: ColumnRef {
+                    index: result.len(),
+                    header: column.header.clone(),
+                },
+                rule,
+                transform,
+                sources,
+            });
+        }
+        for (rule, aggregate) in group.aggregates().iter().enumerate() {
+            let sources = aggregate.column().map(|c| input[c.index].sources.clone());
+            result.push(Lineage {
+                column: ColumnRef {
+                    index: result.len(),
+                    header: aggregate.header().to_string(),
+                },
+                rule,
+                transform: aggregate.function().name(),
+                sources: sources.unwrap_or_default(),
+            });
+        }
+    }
+    result
+}
+
+/// Format of the lineage file
+enum Format {
+    Yaml,
+    Json,
+    Csv,
+}
+
+/// Write the lineage into a file in the format indicated by its extension,
+/// which can be YAML, JSON, or CSV with a row for each source of each column
+///
+/// The returned destination should be committed to move the file into place.
+pub fn write_lineage(path: &Path, lineage: &[Lineage]) -> Result<Destination> {
+    let format = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => Format::Yaml,
+        Some("json") => Format::Json,
+        Some("csv") => Format::Csv,
+        _ => bail!("unknown lineage format: {}", path.display()),
+    };
+    let destination = Destination::create(Some(path)).context("create lineage file")?;
+    write(format, destination.clone(), lineage).context("write lineage")?;
+    Ok(destination)
+}
+
+fn write(format: Format, writer: impl Write, lineage: &[Lineage]) -> Result<()> {
+    match format {
+        Format::Yaml => serde_yaml::to_writer(writer, lineage)?,
+        Format::Json => serde_json::to_writer_pretty(writer, lineage)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            writer.write_record(["column", "rule", "transform", "source"])?;
+            for entry in lineage.iter() {
+                let column = entry.column.to_string();
+                let rule = entry.rule.to_string();
+                // Columns without sources, e.g. counts, still get a row with an empty source
+                let sources = entry.sources.iter().map(|source| source.to_string());
+                let sources = sources.pad_using(1, |_| String::new());
+                for source in sources {
+                    writer.write_record([&column, &rule, entry.transform, &source])?;
+                }
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_lineage() {
+        let transformation = Transformation::from_yaml(
+            r#"
+            stages:
+            - rules:
+              - transform: join
+                header: Name
+                columns: ["A: First", "B: Last"]
+              - "C: Country"
+              - "D: Age"
+            - rules:
+              - "B: Country"
+              - transform: coalesce
+                header: Label
+                columns: ["A: Name", "B: Country"]
+              - "C: Age"
+            group:
+              by: ["A: Country"]
+              aggregates:
+              - header: Responses
+                function: count
+              - header: Labels
+                function: join-distinct
+                column: "B: Label"
+              - header: Oldest
+                function: max
+                column: "C: Age"
+            "#
+            .as_bytes(),
+        )
+        .unwrap();
+        let mut buffer = vec![];
+        write(Format::Csv, &mut buffer, &lineage(&transformation)).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "column,rule,transform,source\n\
+             A: Country,0,original,C: Country\n\
+             B: Responses,0,count,\n\
+             C: Labels,1,join-distinct,A: First\n\
+             C: Labels,1,join-distinct,B: Last\n\
+             C: Labels,1,join-distinct,C: Country\n\
+             D: Oldest,2,max,D: Age\n",
+        );
+    }
+}
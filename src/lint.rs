@@ -0,0 +1,115 @@
+use csv_transformer::transform::{Transform, TransformedColumns};
+use csv_transformer::Transformation;
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// Find suspicious things in each stage of the transformation
+pub fn lint_transformation(transformation: &Transformation, headers: &[String]) -> Vec<String> {
+    let stages = transformation.stages();
+    let mut headers = headers.to_vec();
+    let mut warnings = vec![];
+    for (i, stage) in stages.iter().enumerate() {
+        let stage_warnings = lint(stage.rules(), &headers);
+        if stages.len() == 1 {
+            warnings.extend(stage_warnings);
+        } else {
+            let name = stage
+                .name()
+                .map_or_else(|| i.to_string(), |n| format!("`{}`", n));
+            let stage_warnings = stage_warnings.into_iter();
+            warnings.extend(stage_warnings.map(|w| format!("stage {}: {}", name, w)));
+        }
+        headers = stage.headers().into_iter().map(str::to_string).collect();
+    }
+    warnings
+}
+
+/// Find suspicious things in the rules which are not errors
+///
+/// It checks across all the rules for columns in the original data never used,
+/// output headers produced multiple times, and columns used by multiple rules.
+pub fn lint(new_columns: &[TransformedColumns], headers: &[String]) -> Vec<String> {
+    let mut warnings = vec![];
+    let mut rules_by_column = HashMap::new();
+    let mut rules_by_header = HashMap::new();
+    for (i, col) in new_columns.iter().enumerate() {
+        for column in col.columns() {
+            rules_by_column
+                .entry(column.index)
+                .or_insert_with(Vec::new)
+                .push(i);
+        }
+        for header in col.headers() {
+            rules_by_header
+                .entry(header)
+                .or_insert_with(Vec::new)
+                .push(i);
+        }
+        for warning in col.warnings() {
+            warnings.push(format!("rule {}: {}", i, warning));
+        }
+    }
+
+    for (index, header) in headers.iter().enumerate() {
+        if !rules_by_column.contains_key(&index) {
+            warnings.push(format!("column `{}` isn't used by any rule", header));
+        }
+    }
+    for (index, rules) in rules_by_column.iter().sorted_by_key(|(index, _)| **index) {
+        let rules = rules.iter().unique().collect_vec();
+        if rules.len() > 1 {
+            warnings.push(format!(
+                "column `{}` is used by multiple rules: {}",
+                headers[*index],
+                rules.iter().join(", "),
+            ));
+        }
+    }
+    for (header, rules) in rules_by_header
+        .iter()
+        .sorted_by(|(a, x), (b, y)| x.cmp(y).then_with(|| a.cmp(b)))
+    {
+        if rules.len() > 1 {
+            warnings.push(format!(
+                "output header `{}` is produced multiple times by rules: {}",
+                header,
+                rules.iter().unique().join(", "),
+            ));
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_lint() {
+        let headers = ["Q1", "Q2", "Q3", "Q4"].iter().map(|h| h.to_string());
+        let new_columns: Vec<TransformedColumns> = serde_yaml::from_str(
+            r#"
+            - "A: Q1"
+            - transform: rename
+              header: Q2
+              column: "B: Q2"
+            - transform: join
+              header: Q1
+              columns: ["A: Q1", "C: Q3"]
+            - transform: transpose
+              sources: {"D: Q4": x}
+              columns: {Q2: x, Q1: y}
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            lint(&new_columns, &headers.collect_vec()),
+            [
+                "rule 1: rename header is the same as the original: Q2",
+                "column `Q1` is used by multiple rules: 0, 2",
+                "output header `Q1` is produced multiple times by rules: 0, 2, 3",
+                "output header `Q2` is produced multiple times by rules: 1, 3",
+            ],
+        );
+    }
+}
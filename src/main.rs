@@ -1,124 +1,530 @@
-use crate::column_ref::ColumnRef;
-use crate::transform::{Transform, TransformedColumns};
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+use csv_transformer::{ColumnRef, Group, Transformation, Value, ValueType};
+use dedupe::Dedupe;
+use input::{Input, ReadOptions};
 use itertools::Itertools;
+use output::{Destination, Output};
+use sorter::Sorter;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
-use std::iter;
 use std::path::{Path, PathBuf};
-use std::str;
+use std::str::FromStr;
 use structopt::StructOpt;
 
-mod column_ref;
-mod transform;
+mod compression;
+mod dedupe;
+mod input;
+mod lineage;
+mod lint;
+mod output;
+mod profile;
+mod schema;
+mod sorter;
+mod suggest;
+
+/// Number of sample values to show for each column in annotated extract result
+const ANNOTATE_SAMPLES: usize = 3;
 
 #[derive(StructOpt)]
 enum Action {
     Extract {
-        #[structopt(parse(from_os_str))]
-        original: PathBuf,
+        #[structopt(flatten)]
+        input: Input,
+        /// Annotate each column with samples and statistics of its values
+        #[structopt(short, long)]
+        annotate: bool,
+        /// Write the result into the given file instead of stdout
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
     },
     Transform {
+        #[structopt(flatten)]
+        input: Input,
+        #[structopt(flatten)]
+        dedupe: Dedupe,
         #[structopt(parse(from_os_str))]
-        original: PathBuf,
+        transform: PathBuf,
+        #[structopt(flatten)]
+        output: Output,
+        /// Write the source columns of each output column into the given YAML, JSON or CSV file
+        #[structopt(long, parse(from_os_str))]
+        lineage: Option<PathBuf>,
+        /// Write the output of a stage into a CSV file, in the form of `stage=path`
+        /// where the stage is either its name or its index
+        #[structopt(long, number_of_values = 1)]
+        dump_stage: Vec<StageDump>,
+        /// Fail if the output headers differ from those in the given file,
+        /// either a YAML list, or a CSV file with a header row
+        #[structopt(long, parse(from_os_str))]
+        expect_headers: Option<PathBuf>,
+    },
+    Check {
+        #[structopt(flatten)]
+        input: Input,
+        #[structopt(flatten)]
+        dedupe: Dedupe,
         #[structopt(parse(from_os_str))]
         transform: PathBuf,
+        /// Fail if the output headers differ from those in the given file,
+        /// either a YAML list, or a CSV file with a header row
+        #[structopt(long, parse(from_os_str))]
+        expect_headers: Option<PathBuf>,
+    },
+    Merge {
+        /// File with the expected headers, either a YAML list, or a CSV file with a header row
+        #[structopt(long, parse(from_os_str))]
+        schema: PathBuf,
+        /// An original file followed by its transformation file, can be given multiple times
+        #[structopt(
+            long,
+            number_of_values = 2,
+            value_names = &["original", "transform"],
+            required = true,
+            parse(from_os_str)
+        )]
+        source: Vec<PathBuf>,
+        /// Add a column with the given header before the others,
+        /// holding the file name of the original file each record comes from
+        #[structopt(long)]
+        tag_column: Option<String>,
+        #[structopt(flatten)]
+        options: ReadOptions,
+        #[structopt(flatten)]
+        output: Output,
+    },
+    Suggest {
+        #[structopt(flatten)]
+        input: Input,
     },
+    Profile {
+        #[structopt(flatten)]
+        input: Input,
+        /// Format of the output, `yaml` or `json`
+        #[structopt(long, default_value = "yaml")]
+        format: DocumentFormat,
+        /// Number of most frequent values to list for each column
+        #[structopt(long, default_value = "5")]
+        top: usize,
+    },
+}
+
+enum DocumentFormat {
+    Yaml,
+    Json,
+}
+
+impl FromStr for DocumentFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "yaml" => Ok(DocumentFormat::Yaml),
+            "json" => Ok(DocumentFormat::Json),
+            _ => bail!("unknown format: {}", s),
+        }
+    }
+}
+
+struct StageDump {
+    stage: String,
+    path: PathBuf,
+}
+
+impl FromStr for StageDump {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (stage, path) = s
+            .split_once('=')
+            .with_context(|| format!("expected `stage=path`: {}", s))?;
+        Ok(StageDump {
+            stage: stage.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
 }
 
 fn main() -> Result<()> {
     match Action::from_args() {
-        Action::Extract { original } => do_extract(&original),
+        Action::Extract {
+            input,
+            annotate,
+            output,
+        } => do_extract(&input, annotate, output.as_deref()),
         Action::Transform {
-            original,
+            input,
+            dedupe,
+            transform,
+            output,
+            lineage,
+            dump_stage,
+            expect_headers,
+        } => do_transform(
+            &input,
+            &dedupe,
+            &transform,
+            &output,
+            lineage.as_deref(),
+            &dump_stage,
+            expect_headers.as_deref(),
+        ),
+        Action::Check {
+            input,
+            dedupe,
             transform,
-        } => do_transform(&original, &transform),
+            expect_headers,
+        } => do_check(&input, &dedupe, &transform, expect_headers.as_deref()),
+        Action::Merge {
+            schema,
+            source,
+            tag_column,
+            options,
+            output,
+        } => do_merge(&schema, &source, tag_column.as_deref(), &options, &output),
+        Action::Suggest { input } => do_suggest(&input),
+        Action::Profile { input, format, top } => do_profile(&input, format, top),
     }
 }
 
-fn do_extract(original: &Path) -> Result<()> {
-    let data = parse_csv(original).context("parse original file")?;
+fn do_extract(input: &Input, annotate: bool, output: Option<&Path>) -> Result<()> {
+    let data = input.read()?;
+    let destination = Destination::create(output)?;
+    if annotate {
+        let profiles = profile::profile(&data, ANNOTATE_SAMPLES);
+        profile::write_annotated_columns(&profiles, destination.clone())
+            .context("write extract result")?;
+        return destination.commit();
+    }
     let columns = data
         .headers
         .into_iter()
         .enumerate()
         .map(|(index, header)| ColumnRef { index, header })
         .collect_vec();
-    let stdout = io::stdout();
-    let stdout = stdout.lock();
-    serde_yaml::to_writer(stdout, &columns).context("write extract result")?;
-    Ok(())
+    serde_yaml::to_writer(destination.clone(), &columns).context("write extract result")?;
+    destination.commit()
 }
 
-fn do_transform(original: &Path, transform: &Path) -> Result<()> {
-    let original_data = parse_csv(original).context("parse original file")?;
-    let transform = File::open(transform).context("open transform file")?;
-    let transform = BufReader::new(transform);
-    let new_columns: Vec<TransformedColumns> =
-        serde_yaml::from_reader(transform).context("parse transform file")?;
+fn do_transform(
+    input: &Input,
+    dedupe: &Dedupe,
+    transform: &Path,
+    output: &Output,
+    lineage: Option<&Path>,
+    dump_stages: &[StageDump],
+    expect_headers: Option<&Path>,
+) -> Result<()> {
+    let transformation = load_transformation(transform)?;
+    if let Some(path) = expect_headers {
+        check_headers(&transformation, path)?;
+    }
+    let (mut original_data, missing) = input.read_with_missing()?;
+    let removed = dedupe.apply(&mut original_data)?;
+    if dedupe.is_enabled() {
+        eprintln!("removed {} duplicate records", removed.len());
+    }
+    transformation.validate(&original_data.headers)?;
+    missing.check(
+        transformation
+            .source_columns()
+            .into_iter()
+            .chain(dedupe.columns()),
+    )?;
+    for warning in lint::lint_transformation(&transformation, &original_data.headers) {
+        eprintln!("warning: {}", warning);
+    }
+    let lineage = lineage
+        .map(|path| lineage::write_lineage(path, &lineage::lineage(&transformation)))
+        .transpose()?;
 
-    // Check all the column references
-    new_columns
-        .iter()
-        .map(|c| {
-            c.validate(|col| {
-                let is_valid = original_data
-                    .headers
-                    .get(col.index)
-                    .map(|header| header == &col.header)
-                    .unwrap_or(false);
-                ensure!(is_valid, "invalid column reference: {}", col);
-                Ok(())
-            })
-        })
-        .collect::<Result<()>>()?;
+    // Open the files to dump intermediate stages into
+    let stages = transformation.stages();
+    let mut dumps = vec![];
+    for dump in dump_stages {
+        let index = stages
+            .iter()
+            .position(|stage| stage.name() == Some(&dump.stage))
+            .or_else(|| dump.stage.parse().ok().filter(|i| *i < stages.len()))
+            .with_context(|| format!("unknown stage: {}", dump.stage))?;
+        let destination = Destination::create(Some(&dump.path))
+            .with_context(|| format!("create stage dump {}", dump.path.display()))?;
+        let mut writer = csv::Writer::from_writer(destination.clone());
+        writer
+            .write_record(stages[index].headers())
+            .context("write stage header")?;
+        dumps.push((index, writer, destination));
+    }
 
     // Output the result
-    let stdout = io::stdout();
-    let stdout = stdout.lock();
-    let mut writer = csv::Writer::from_writer(stdout);
-    // Write the header
-    for col in new_columns.iter() {
-        col.write_headers(&mut writer)?;
-    }
-    writer
-        .write_record(iter::empty::<&[u8]>())
-        .context("write header")?;
-    // Write the records
-    for (i, record) in original_data.values.iter().enumerate() {
-        for col in new_columns.iter() {
-            col.write_fields(&record, &mut writer)
-                .with_context(|| format!("transform record {}", i))?;
+    let headers = transformation.headers();
+    let headers = headers.into_iter().map(str::to_string).collect();
+    let mut sink = output.open(headers, transformation.column_types())?;
+    let limit = output.limit.unwrap_or(usize::MAX);
+    let mut aggregator = transformation.group().map(Group::aggregator);
+    let mut sorter =
+        Some(Sorter::new(&transformation)).filter(|_| !transformation.sort().is_empty());
+    // All the records need to be transformed to find the first ones when sorted or grouped
+    let unsorted_limit = if sorter.is_some() { usize::MAX } else { limit };
+    let record_limit = if aggregator.is_some() {
+        usize::MAX
+    } else {
+        unsorted_limit
+    };
+    let mut write = |record: &[String], output: Vec<Value>| match &mut sorter {
+        Some(sorter) => sorter.push(transformation.sort_key(record, &output)?, output),
+        None => sink.write_record(&output),
+    };
+    let records = original_data.values.iter().enumerate();
+    let records = records.take(record_limit);
+    for (i, record) in records {
+        let mut outputs = transformation
+            .apply_stages(record)
+            .with_context(|| format!("transform record {}", i))?;
+        for (index, dump, _) in dumps.iter_mut() {
+            let record = outputs[*index].iter().map(|value| value.to_string());
+            dump.write_record(record).context("write stage record")?;
         }
-        writer
-            .write_record(iter::empty::<&[u8]>())
-            .context("write record")?;
+        let output = outputs.pop().unwrap();
+        match &mut aggregator {
+            Some(aggregator) => aggregator
+                .add(&output)
+                .with_context(|| format!("aggregate record {}", i))?,
+            None => write(record, output).with_context(|| format!("output record {}", i))?,
+        }
+    }
+    if let Some(aggregator) = aggregator {
+        let groups = aggregator.finish().into_iter().enumerate();
+        for (i, output) in groups.take(unsorted_limit) {
+            write(&[], output).with_context(|| format!("output group {}", i))?;
+        }
+    }
+    if let Some(sorter) = sorter {
+        sorter.finish(limit, |record| sink.write_record(record))?;
+    }
+    sink.finish()?;
+    // Only leave the other files once the result is written successfully
+    for (_, mut dump, destination) in dumps {
+        dump.flush().context("write stage dump")?;
+        destination.commit()?;
+    }
+    if let Some(destination) = lineage {
+        destination.commit()?;
+    }
+    dedupe.write_duplicates(&original_data.headers, &removed)
+}
+
+fn do_check(
+    input: &Input,
+    dedupe: &Dedupe,
+    transform: &Path,
+    expect_headers: Option<&Path>,
+) -> Result<()> {
+    let transformation = load_transformation(transform)?;
+    if let Some(path) = expect_headers {
+        check_headers(&transformation, path)?;
     }
+    ensure!(
+        dedupe.duplicates().is_none(),
+        "--duplicates can't be used with check",
+    );
+    let (mut original_data, missing) = input.read_with_missing()?;
+    let removed = dedupe.apply(&mut original_data)?.len();
+    transformation.validate(&original_data.headers)?;
+    missing.check(
+        transformation
+            .source_columns()
+            .into_iter()
+            .chain(dedupe.columns()),
+    )?;
 
+    let mut warnings = lint::lint_transformation(&transformation, &original_data.headers);
+    if original_data.values.is_empty() {
+        warnings.push("original file has no records".to_string());
+    }
+    // Transform all the records without writing the result anywhere
+    let mut failed = 0;
+    let mut aggregator = transformation.group().map(Group::aggregator);
+    let outputs = transformation.apply_records(&original_data.values);
+    for (i, (record, output)) in original_data.values.iter().zip(outputs).enumerate() {
+        let result = output.and_then(|output| match &mut aggregator {
+            Some(aggregator) => aggregator
+                .add(&output)
+                .with_context(|| format!("aggregate record {}", i)),
+            None => transformation
+                .sort_key(record, &output)
+                .map(|_| ())
+                .with_context(|| format!("sort record {}", i)),
+        });
+        if let Err(e) = result {
+            eprintln!("error: {:#}", e);
+            failed += 1;
+        }
+    }
+    let groups = aggregator.map(|aggregator| aggregator.finish());
+    for (i, output) in groups.iter().flatten().enumerate() {
+        if let Err(e) = transformation.sort_key(&[], output) {
+            eprintln!("error: sort group {}: {:#}", i, e);
+            failed += 1;
+        }
+    }
+
+    for warning in warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
+    let stages = transformation.stages();
+    if stages.len() > 1 {
+        println!("stages: {}", stages.len());
+    }
+    let rules: usize = stages.iter().map(|stage| stage.rules().len()).sum();
+    println!("rules: {}", rules);
+    println!("output columns: {}", transformation.headers().len());
+    if dedupe.is_enabled() {
+        println!("duplicates removed: {}", removed);
+    }
+    println!("rows processed: {}", original_data.values.len());
+    if let Some(groups) = &groups {
+        println!("groups: {}", groups.len());
+    }
+    println!("warnings: {}", warnings.len());
+    println!("errors: {}", failed);
+    ensure!(failed == 0, "failed to transform {} records", failed);
     Ok(())
 }
 
-struct CsvData {
-    headers: Vec<String>,
-    values: Vec<Vec<String>>,
+/// Check the output headers of the transformation against the expected ones in the file
+fn check_headers(transformation: &Transformation, path: &Path) -> Result<()> {
+    let expected = schema::read_headers(path)?;
+    let differences = schema::diff(&expected, &transformation.headers());
+    for difference in differences.iter() {
+        eprintln!("error: {}", difference);
+    }
+    ensure!(
+        differences.is_empty(),
+        "output headers don't match {}",
+        path.display(),
+    );
+    Ok(())
 }
 
-fn parse_csv(path: &Path) -> Result<CsvData> {
-    let mut reader = csv::Reader::from_path(path).context("open csv file")?;
-    let headers = reader
-        .headers()
-        .context("read headers")?
+fn do_merge(
+    schema: &Path,
+    sources: &[PathBuf],
+    tag_column: Option<&str>,
+    options: &ReadOptions,
+    output: &Output,
+) -> Result<()> {
+    let schema = schema::read_headers(schema)?;
+
+    // Check all the sources before transforming any of them
+    let mut loaded = vec![];
+    let mut mismatched = 0;
+    for source in sources.chunks(2) {
+        let (original, transform) = (&source[0], &source[1]);
+        let original_data = options
+            .read_file(original)
+            .with_context(|| format!("read {}", original.display()))?;
+        let transformation = load_transformation(transform)
+            .with_context(|| format!("load {}", transform.display()))?;
+        transformation
+            .validate(&original_data.headers)
+            .with_context(|| format!("validate {}", transform.display()))?;
+        ensure!(
+            transformation.group().is_none(),
+            "{}: group can't be used when merging",
+            transform.display(),
+        );
+        let mut warnings = lint::lint_transformation(&transformation, &original_data.headers);
+        if !transformation.sort().is_empty() {
+            warnings.push("sort is ignored when merging".to_string());
+        }
+        for warning in warnings {
+            eprintln!("warning: {}: {}", transform.display(), warning);
+        }
+        let differences = schema::diff(&schema, &transformation.headers());
+        for difference in differences.iter() {
+            eprintln!("error: {}: {}", transform.display(), difference);
+        }
+        if !differences.is_empty() {
+            mismatched += 1;
+        }
+        let tag = original.file_name().unwrap_or_default();
+        let tag = tag.to_string_lossy().into_owned();
+        loaded.push((tag, transform, original_data, transformation));
+    }
+    ensure!(
+        mismatched == 0,
+        "headers of {} out of {} transformations don't match the schema",
+        mismatched,
+        loaded.len(),
+    );
+
+    // The declared types of each column should be the same in all the transformations
+    let (_, first, _, transformation) = &loaded[0];
+    let mut types = transformation.column_types();
+    let type_name =
+        |ty: &Option<ValueType>| ty.map_or("undeclared".to_string(), |ty| ty.to_string());
+    for (_, transform, _, transformation) in loaded[1..].iter() {
+        let other = transformation.column_types();
+        for ((header, a), b) in schema.iter().zip(types.iter()).zip(other.iter()) {
+            ensure!(
+                a == b,
+                "{}: type of column `{}` is {}, but it's {} in {}",
+                transform.display(),
+                header,
+                type_name(b),
+                type_name(a),
+                first.display(),
+            );
+        }
+    }
+
+    // Output the result
+    let mut headers = schema;
+    if let Some(tag_column) = tag_column {
+        headers.insert(0, tag_column.to_string());
+        types.insert(0, None);
+    }
+    let mut sink = output.open(headers, types)?;
+    let records = loaded
         .iter()
-        .map(|header| header.to_string())
-        .collect();
-    let values = reader
-        .records()
-        .enumerate()
-        .map(|(i, record)| {
-            let record = record.with_context(|| format!("read record {}", i))?;
-            Ok(record.iter().map(|r| r.to_string()).collect())
-        })
-        .collect::<Result<_>>()?;
-    Ok(CsvData { headers, values })
+        .flat_map(|(tag, _, original_data, transformation)| {
+            let records = transformation.apply_records(&original_data.values);
+            records.map(move |record| (tag, record))
+        });
+    for (tag, record) in records.take(output.limit.unwrap_or(usize::MAX)) {
+        let mut record = record.with_context(|| format!("transform {}", tag))?;
+        if tag_column.is_some() {
+            record.insert(0, Value::from(tag.as_str()));
+        }
+        sink.write_record(&record)?;
+    }
+    sink.finish()
+}
+
+fn load_transformation(path: &Path) -> Result<Transformation> {
+    let transform = File::open(path).context("open transform file")?;
+    let transform = BufReader::new(transform);
+    Transformation::from_yaml(transform).context("parse transform file")
+}
+
+fn do_suggest(input: &Input) -> Result<()> {
+    let data = input.read()?;
+    let rules = suggest::suggest(&data);
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    serde_yaml::to_writer(stdout, &rules).context("write suggest result")?;
+    Ok(())
+}
+
+fn do_profile(input: &Input, format: DocumentFormat, top: usize) -> Result<()> {
+    let data = input.read()?;
+    let profiles = profile::profile(&data, top);
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    match format {
+        DocumentFormat::Yaml => serde_yaml::to_writer(stdout, &profiles)?,
+        DocumentFormat::Json => serde_json::to_writer_pretty(stdout, &profiles)?,
+    }
+    Ok(())
 }
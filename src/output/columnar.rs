@@ -0,0 +1,352 @@
+use super::Sink;
+use anyhow::{Context, Result};
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use csv_transformer::{Value, ValueType};
+use itertools::Itertools;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Number of records in each batch written into the file
+const BATCH_SIZE: usize = 65536;
+
+#[derive(Clone, Copy)]
+pub enum ColumnarFormat {
+    Parquet,
+    /// Arrow IPC file format, a.k.a. Feather V2
+    Arrow,
+}
+
+/// Sink writing the records into a columnar file
+///
+/// If all the columns have declared types, records are written in batches as they come.
+/// Otherwise they are kept in memory until finishing, so that the type of columns
+/// without declared type can be inferred from all the values.
+pub struct ColumnarSink<W: Write + Send> {
+    headers: Vec<String>,
+    /// Records not written into the file yet
+    records: Vec<Vec<Value>>,
+    state: State<W>,
+}
+
+enum State<W: Write + Send> {
+    /// Waiting for all the records to infer the types of undeclared columns
+    Pending {
+        format: ColumnarFormat,
+        types: Vec<Option<ValueType>>,
+        writer: W,
+    },
+    Open(Box<BatchWriter<W>>),
+}
+
+impl<W: Write + Send> ColumnarSink<W> {
+    pub fn new(
+        format: ColumnarFormat,
+        headers: Vec<String>,
+        types: Vec<Option<ValueType>>,
+        writer: W,
+    ) -> Result<Self> {
+        let state = match types.iter().copied().collect::<Option<Vec<_>>>() {
+            Some(types) => {
+                let writer = BatchWriter::open(format, &headers, types, writer)?;
+                State::Open(Box::new(writer))
+            }
+            None => State::Pending {
+                format,
+                types,
+                writer,
+            },
+        };
+        Ok(ColumnarSink {
+            headers,
+            records: vec![],
+            state,
+        })
+    }
+}
+
+impl<W: Write + Send> Sink for ColumnarSink<W> {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        self.records.push(record.to_vec());
+        if let State::Open(writer) = &mut self.state {
+            if self.records.len() >= BATCH_SIZE {
+                writer.write(&self.headers, self.records.drain(..))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        let ColumnarSink {
+            headers,
+            mut records,
+            state,
+        } = *self;
+        let mut writer = match state {
+            State::Open(writer) => *writer,
+            State::Pending {
+                format,
+                types,
+                writer,
+            } => {
+                let types = types.iter().enumerate().map(|(col, ty)| {
+                    let values = records.iter().map(|record| &record[col]);
+                    ty.unwrap_or_else(|| infer_type(values))
+                });
+                BatchWriter::open(format, &headers, types.collect(), writer)?
+            }
+        };
+        while !records.is_empty() {
+            let end = records.len().min(BATCH_SIZE);
+            writer.write(&headers, records.drain(..end))?;
+        }
+        writer.finish()
+    }
+}
+
+/// Writer of the file from batches of records converted into the types of the columns
+struct BatchWriter<W: Write + Send> {
+    types: Vec<ValueType>,
+    schema: Arc<Schema>,
+    writer: FileWriter<W>,
+}
+
+enum FileWriter<W: Write + Send> {
+    Parquet(ArrowWriter<W>),
+    Arrow(arrow_ipc::writer::FileWriter<W>),
+}
+
+impl<W: Write + Send> BatchWriter<W> {
+    fn open(
+        format: ColumnarFormat,
+        headers: &[String],
+        types: Vec<ValueType>,
+        writer: W,
+    ) -> Result<Self> {
+        let fields = headers.iter().zip(types.iter()).map(|(header, ty)| {
+            let data_type = match ty {
+                ValueType::Text => DataType::Utf8,
+                ValueType::Integer => DataType::Int64,
+                ValueType::Float => DataType::Float64,
+                ValueType::Boolean => DataType::Boolean,
+            };
+            Field::new(header, data_type, true)
+        });
+        let schema = Arc::new(Schema::new(fields.collect::<Vec<_>>()));
+        let writer = match format {
+            ColumnarFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let writer = ArrowWriter::try_new(writer, schema.clone(), Some(properties))?;
+                FileWriter::Parquet(writer)
+            }
+            ColumnarFormat::Arrow => {
+                FileWriter::Arrow(arrow_ipc::writer::FileWriter::try_new(writer, &schema)?)
+            }
+        };
+        Ok(BatchWriter {
+            types,
+            schema,
+            writer,
+        })
+    }
+
+    fn write(
+        &mut self,
+        headers: &[String],
+        records: impl ExactSizeIterator<Item = Vec<Value>>,
+    ) -> Result<()> {
+        let len = records.len();
+        let mut columns = self
+            .types
+            .iter()
+            .map(|_| Vec::with_capacity(len))
+            .collect_vec();
+        for record in records {
+            for (col, value) in record.into_iter().enumerate() {
+                let value = value
+                    .convert(self.types[col])
+                    .with_context(|| format!("convert value of column `{}`", headers[col]))?;
+                columns[col].push(value);
+            }
+        }
+        let columns = self.types.iter().zip(columns);
+        let columns = columns.map(|(ty, values)| to_array(*ty, values)).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        match &mut self.writer {
+            FileWriter::Parquet(writer) => writer.write(&batch).context("write parquet"),
+            FileWriter::Arrow(writer) => writer.write(&batch).context("write arrow"),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        let mut writer = match self.writer {
+            FileWriter::Parquet(writer) => writer.into_inner().context("write parquet")?,
+            FileWriter::Arrow(writer) => writer.into_inner().context("write arrow")?,
+        };
+        writer.flush().context("flush output")
+    }
+}
+
+/// Infer the type of a column from its values, which is the first of
+/// integer, float and boolean that all the values can be converted to and back as is,
+/// or text otherwise
+fn infer_type<'a>(values: impl Iterator<Item = &'a Value> + Clone) -> ValueType {
+    let types = [ValueType::Integer, ValueType::Float, ValueType::Boolean];
+    let mut values = values
+        .filter(|value| !value.to_string().is_empty())
+        .peekable();
+    if values.peek().is_none() {
+        return ValueType::Text;
+    }
+    let found = types.iter().copied().find(|ty| {
+        values
+            .clone()
+            .all(|value| match value.clone().convert(*ty) {
+                Ok(converted) => converted.to_string() == value.to_string(),
+                Err(_) => false,
+            })
+    });
+    found.unwrap_or(ValueType::Text)
+}
+
+fn to_array(ty: ValueType, values: Vec<Value>) -> ArrayRef {
+    let values = values.into_iter();
+    match ty {
+        ValueType::Text => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Null => None,
+                    value => Some(value.to_string()),
+                })
+                .collect::<StringArray>(),
+        ),
+        ValueType::Integer => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Integer(i) => Some(i),
+                    _ => None,
+                })
+                .collect::<Int64Array>(),
+        ),
+        ValueType::Float => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Float(x) => Some(x),
+                    _ => None,
+                })
+                .collect::<Float64Array>(),
+        ),
+        ValueType::Boolean => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Boolean(b) => Some(b),
+                    _ => None,
+                })
+                .collect::<BooleanArray>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, Int64Type};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_parquet() {
+        let headers = ["ID", "Age", "Score", "Rust?", "Comment"];
+        let headers = headers.iter().map(|h| h.to_string()).collect();
+        let types = vec![None, None, None, None, Some(ValueType::Text)];
+        let name = format!(
+            "csv-transformer-write-parquet.{}.parquet",
+            std::process::id()
+        );
+        let path = std::env::temp_dir().join(name);
+        let file = std::fs::File::create(&path).unwrap();
+        let mut sink =
+            Box::new(ColumnarSink::new(ColumnarFormat::Parquet, headers, types, file).unwrap());
+        let records: [[Value; 5]; 2] = [
+            [
+                "007".into(),
+                "30".into(),
+                "4.5".into(),
+                "true".into(),
+                "1".into(),
+            ],
+            ["010".into(), "".into(), "3".into(), "no".into(), "".into()],
+        ];
+        for record in records.iter() {
+            sink.write_record(record).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let batch = reader.into_iter().next().unwrap().unwrap();
+        let schema = batch.schema();
+        let data_types = schema.fields().iter().map(|f| f.data_type().clone());
+        assert_eq!(
+            data_types.collect::<Vec<_>>(),
+            [
+                DataType::Utf8,
+                DataType::Int64,
+                DataType::Float64,
+                DataType::Utf8,
+                DataType::Utf8,
+            ],
+        );
+        let ages = batch.column(1).as_primitive::<Int64Type>();
+        assert_eq!(ages.iter().collect::<Vec<_>>(), [Some(30), None]);
+        let scores = batch.column(2).as_primitive::<Float64Type>();
+        assert_eq!(scores.iter().collect::<Vec<_>>(), [Some(4.5), Some(3.0)]);
+        let ids = batch.column(0).as_string::<i32>();
+        assert_eq!(ids.iter().collect::<Vec<_>>(), [Some("007"), Some("010")]);
+        let comments = batch.column(4).as_string::<i32>();
+        assert_eq!(comments.iter().collect::<Vec<_>>(), [Some("1"), None]);
+    }
+
+    #[test]
+    fn write_arrow() {
+        let headers = vec!["ID".to_string(), "Rust?".to_string()];
+        let types = vec![Some(ValueType::Integer), Some(ValueType::Boolean)];
+        let mut buffer = vec![];
+        let mut sink = Box::new(
+            ColumnarSink::new(ColumnarFormat::Arrow, headers, types, &mut buffer).unwrap(),
+        );
+        // Declared columns are written in batches as records come
+        for id in 0..=BATCH_SIZE {
+            let rust = if id % 2 == 0 { "yes" } else { "" };
+            sink.write_record(&[id.to_string().into(), rust.into()])
+                .unwrap();
+        }
+        assert_eq!(sink.records.len(), 1);
+        sink.finish().unwrap();
+
+        let reader = arrow_ipc::reader::FileReader::try_new(Cursor::new(buffer), None).unwrap();
+        let schema = reader.schema();
+        let data_types = schema.fields().iter().map(|f| f.data_type().clone());
+        assert_eq!(
+            data_types.collect::<Vec<_>>(),
+            [DataType::Int64, DataType::Boolean],
+        );
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        let sizes = batches.iter().map(|batch| batch.num_rows());
+        assert_eq!(sizes.collect::<Vec<_>>(), [BATCH_SIZE, 1]);
+        let ids = batches[1].column(0).as_primitive::<Int64Type>();
+        assert_eq!(ids.iter().collect::<Vec<_>>(), [Some(BATCH_SIZE as i64)]);
+        let rust = batches[0].column(1).as_boolean();
+        assert_eq!(rust.iter().take(2).collect::<Vec<_>>(), [Some(true), None]);
+    }
+}
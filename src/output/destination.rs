@@ -0,0 +1,123 @@
+use crate::compression::{Compression, Encoder};
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Where the output is written, which is either stdout, or a file compressed
+/// according to its extension
+///
+/// A file is written into a temporary file next to it first, which is moved to
+/// the path only when committed, so that a failed run never leaves a partial file.
+/// It can be sent to other threads, as required by some writers, e.g. Parquet's.
+#[derive(Clone)]
+pub struct Destination(Arc<Mutex<State>>);
+
+struct State {
+    encoder: Option<Encoder<Target>>,
+    file: Option<TempFile>,
+}
+
+enum Target {
+    Stdout(Stdout),
+    File(BufWriter<File>),
+}
+
+/// Temporary file to be moved to the path, which is removed if it's not moved
+struct TempFile {
+    temp: PathBuf,
+    path: PathBuf,
+}
+
+impl Destination {
+    /// Write into the file at the given path, or stdout if it's `None` or `-`
+    pub fn create(path: Option<&Path>) -> Result<Self> {
+        let path = path.filter(|path| *path != Path::new("-"));
+        let (encoder, file) = match path {
+            None => (Encoder::None(Target::Stdout(io::stdout())), None),
+            Some(path) => {
+                let name = path.file_name().context("output path should be a file")?;
+                let temp_name = format!(".{}.{}.tmp", name.to_string_lossy(), process::id());
+                let temp = path.with_file_name(temp_name);
+                let writer = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&temp)
+                    .with_context(|| format!("create {}", temp.display()))?;
+                let file = TempFile {
+                    temp,
+                    path: path.to_path_buf(),
+                };
+                let (compression, _) = Compression::detect(path);
+                let encoder = compression.encoder(Target::File(BufWriter::new(writer)))?;
+                (encoder, Some(file))
+            }
+        };
+        let state = State {
+            encoder: Some(encoder),
+            file,
+        };
+        Ok(Destination(Arc::new(Mutex::new(state))))
+    }
+
+    /// Finish writing, and move the file to its path
+    pub fn commit(self) -> Result<()> {
+        let mut state = self.state();
+        let encoder = state.encoder.take().context("output already committed")?;
+        match encoder.finish().context("finish compression")? {
+            Target::Stdout(mut stdout) => stdout.flush().context("flush output")?,
+            Target::File(writer) => {
+                let file = writer.into_inner().map_err(|e| e.into_error())?;
+                file.sync_all().context("sync output file")?;
+                let file = state.file.as_ref().unwrap();
+                fs::rename(&file.temp, &file.path)
+                    .with_context(|| format!("move output to {}", file.path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Write for Destination {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.state().encoder {
+            Some(encoder) => encoder.write(buf),
+            None => Err(io::Error::other("output already committed")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state().encoder {
+            Some(encoder) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for Target {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Target::Stdout(stdout) => stdout.write(buf),
+            Target::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Target::Stdout(stdout) => stdout.flush(),
+            Target::File(file) => file.flush(),
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.temp);
+    }
+}
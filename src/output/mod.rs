@@ -0,0 +1,750 @@
+use self::columnar::{ColumnarFormat, ColumnarSink};
+pub use self::destination::Destination;
+use crate::compression::Compression;
+use anyhow::{bail, ensure, Context, Result};
+use csv_transformer::{Value, ValueType};
+use itertools::Itertools;
+use rusqlite::{params_from_iter, Connection};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+use unicode_width::UnicodeWidthStr;
+
+mod columnar;
+mod destination;
+
+/// Maximum width of a column in a written workbook, in display width of characters
+const MAX_COLUMN_WIDTH: usize = 60;
+
+// Arguments for writing the transformation result
+#[derive(StructOpt)]
+pub struct Output {
+    /// Write the result into the given file instead of stdout,
+    /// compressed if the extension is `.gz` or `.zst`
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+    /// Format of the output, `csv`, `json`, `ndjson`, `xlsx`, `sqlite`, `parquet`, `arrow`,
+    /// `markdown` or `html`, instead of the one from the extension of the output file
+    #[structopt(long)]
+    pub format: Option<OutputFormat>,
+    /// Table to write the result into for SQLite output
+    #[structopt(long)]
+    pub table: Option<String>,
+    /// Append to the table if it already exists for SQLite output
+    #[structopt(long)]
+    pub append: bool,
+    /// Only write the first given number of records, e.g. for a preview
+    #[structopt(long)]
+    pub limit: Option<usize>,
+}
+
+impl Output {
+    /// Create a sink writing records with the given headers to the destination,
+    /// with the declared types of the columns
+    pub fn open(
+        &self,
+        headers: Vec<String>,
+        types: Vec<Option<ValueType>>,
+    ) -> Result<Box<dyn Sink>> {
+        let path = self
+            .output
+            .as_deref()
+            .filter(|path| *path != Path::new("-"));
+        let (compression, extension) = match path {
+            Some(path) => Compression::detect(path),
+            None => (Compression::None, None),
+        };
+        let format = self.format.unwrap_or_else(|| {
+            let format = extension.and_then(|extension| extension.parse().ok());
+            format.unwrap_or(OutputFormat::Csv)
+        });
+        if let OutputFormat::Sqlite = format {
+            let path = path.context("SQLite output requires --output")?;
+            ensure!(
+                compression == Compression::None,
+                "SQLite output can't be compressed",
+            );
+            let table = self
+                .table
+                .as_ref()
+                .context("SQLite output requires --table")?;
+            let sink = SqliteSink::open(path, table, &headers, &types, self.append)?;
+            return Ok(Box::new(sink));
+        }
+        ensure!(
+            self.table.is_none() && !self.append,
+            "--table and --append can only be used with SQLite output",
+        );
+        let destination = Destination::create(path)?;
+        let sink = create(format, headers, types, destination.clone())?;
+        Ok(Box::new(CommittingSink { sink, destination }))
+    }
+}
+
+/// Format of the transformation result
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    Csv,
+    /// A JSON array of objects keyed by output header
+    Json,
+    /// One JSON object keyed by output header per line
+    Ndjson,
+    Xlsx,
+    /// A table in a SQLite database
+    Sqlite,
+    Markdown,
+    /// A standalone HTML document with a table
+    Html,
+    Parquet,
+    /// Arrow IPC file
+    Arrow,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            "xlsx" => Ok(OutputFormat::Xlsx),
+            "sqlite" | "sqlite3" | "db" => Ok(OutputFormat::Sqlite),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" | "htm" => Ok(OutputFormat::Html),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "arrow" | "feather" | "ipc" => Ok(OutputFormat::Arrow),
+            _ => bail!("unknown format: {}", s),
+        }
+    }
+}
+
+/// Destination of the records in the transformation result
+pub trait Sink {
+    fn write_record(&mut self, record: &[Value]) -> Result<()>;
+    /// Write anything remaining after all the records
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Create a sink writing records with the given headers and declared types in the format
+pub fn create<W: Write + Send + 'static>(
+    format: OutputFormat,
+    headers: Vec<String>,
+    types: Vec<Option<ValueType>>,
+    writer: W,
+) -> Result<Box<dyn Sink>> {
+    Ok(match format {
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            writer.write_record(&headers).context("write header")?;
+            Box::new(CsvSink(writer))
+        }
+        OutputFormat::Json => Box::new(JsonSink {
+            headers,
+            writer,
+            count: 0,
+        }),
+        OutputFormat::Ndjson => Box::new(NdjsonSink { headers, writer }),
+        OutputFormat::Xlsx => Box::new(XlsxSink::new(headers, writer)?),
+        OutputFormat::Sqlite => bail!("SQLite output can only be written into a file"),
+        OutputFormat::Markdown | OutputFormat::Html => Box::new(TableSink {
+            format,
+            headers,
+            records: vec![],
+            writer,
+        }),
+        OutputFormat::Parquet => Box::new(ColumnarSink::new(
+            ColumnarFormat::Parquet,
+            headers,
+            types,
+            writer,
+        )?),
+        OutputFormat::Arrow => Box::new(ColumnarSink::new(
+            ColumnarFormat::Arrow,
+            headers,
+            types,
+            writer,
+        )?),
+    })
+}
+
+/// Sink committing the destination after all the records are written
+struct CommittingSink {
+    sink: Box<dyn Sink>,
+    destination: Destination,
+}
+
+impl Sink for CommittingSink {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        self.sink.write_record(record)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.sink.finish()?;
+        self.destination.commit()
+    }
+}
+
+struct CsvSink<W: Write>(csv::Writer<W>);
+
+impl<W: Write> Sink for CsvSink<W> {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        let record = record.iter().map(|value| value.to_string());
+        self.0.write_record(record).context("write record")
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.0.flush().context("flush output")
+    }
+}
+
+struct JsonSink<W: Write> {
+    headers: Vec<String>,
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> Sink for JsonSink<W> {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.writer.write_all(sep.as_bytes())?;
+        serde_json::to_writer(&mut self.writer, &Row(&self.headers, record))
+            .context("write record")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let end = if self.count == 0 { "[]\n" } else { "\n]\n" };
+        self.writer.write_all(end.as_bytes())?;
+        self.writer.flush().context("flush output")
+    }
+}
+
+struct NdjsonSink<W: Write> {
+    headers: Vec<String>,
+    writer: W,
+}
+
+impl<W: Write> Sink for NdjsonSink<W> {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &Row(&self.headers, record))
+            .context("write record")?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush().context("flush output")
+    }
+}
+
+/// Sink writing a workbook with a single sheet, with the header row frozen,
+/// and the width of each column fitting its content
+struct XlsxSink<W: Write> {
+    worksheet: Worksheet,
+    widths: Vec<usize>,
+    rows: u32,
+    writer: W,
+}
+
+impl<W: Write> XlsxSink<W> {
+    fn new(headers: Vec<String>, writer: W) -> Result<Self> {
+        let mut worksheet = Worksheet::new();
+        let bold = Format::new().set_bold();
+        for (col, header) in headers.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, header, &bold)?;
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+        let widths = headers.iter().map(|h| h.width()).collect();
+        Ok(XlsxSink {
+            worksheet,
+            widths,
+            rows: 1,
+            writer,
+        })
+    }
+}
+
+impl<W: Write> Sink for XlsxSink<W> {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        let row = self.rows;
+        for (col, value) in record.iter().enumerate() {
+            let width = &mut self.widths[col];
+            *width = (*width).max(value.to_string().width());
+            let col = col as u16;
+            // Text is always written as string, so that values like `007` are kept as is
+            match value {
+                Value::Null => continue,
+                Value::Text(s) => self.worksheet.write_string(row, col, s)?,
+                Value::Integer(i) => self.worksheet.write_number(row, col, *i as f64)?,
+                Value::Float(x) => self.worksheet.write_number(row, col, *x)?,
+                Value::Boolean(b) => self.worksheet.write_boolean(row, col, *b)?,
+            };
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        for (col, width) in self.widths.iter().enumerate() {
+            let width = (width + 2).min(MAX_COLUMN_WIDTH);
+            self.worksheet.set_column_width(col as u16, width as f64)?;
+        }
+        let mut workbook = Workbook::new();
+        workbook.push_worksheet(self.worksheet);
+        let buffer = workbook.save_to_buffer().context("write workbook")?;
+        self.writer.write_all(&buffer)?;
+        self.writer.flush().context("flush output")
+    }
+}
+
+/// Sink rendering the records as a table in Markdown or HTML,
+/// where columns with only numbers are aligned to the right
+struct TableSink<W: Write> {
+    format: OutputFormat,
+    headers: Vec<String>,
+    records: Vec<Vec<Value>>,
+    writer: W,
+}
+
+impl<W: Write> TableSink<W> {
+    fn write_markdown(&mut self, right_aligned: &[bool]) -> io::Result<()> {
+        let escape = |s: &str| {
+            s.replace('|', "\\|")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        };
+        let headers = self.headers.iter().map(|h| escape(h)).collect_vec();
+        let records = self.records.iter();
+        let records = records
+            .map(|record| record.iter().map(|v| escape(&v.to_string())).collect_vec())
+            .collect_vec();
+        let widths = (0..headers.len())
+            .map(|col| {
+                let cells = records.iter().map(|record| &record[col]);
+                let width = cells.map(|cell| cell.width()).max().unwrap_or(0);
+                width.max(headers[col].width()).max(3)
+            })
+            .collect_vec();
+        let pad = |cell: &str, col: usize| {
+            let padding = " ".repeat(widths[col] - cell.width());
+            if right_aligned[col] {
+                format!("{}{}", padding, cell)
+            } else {
+                format!("{}{}", cell, padding)
+            }
+        };
+        let write_row =
+            |writer: &mut W, cells: Vec<String>| writeln!(writer, "| {} |", cells.join(" | "));
+        let header_cells = headers.iter().enumerate().map(|(col, h)| pad(h, col));
+        write_row(&mut self.writer, header_cells.collect())?;
+        let separators = widths.iter().zip(right_aligned).map(|(width, right)| {
+            if *right {
+                format!("{}:", "-".repeat(width - 1))
+            } else {
+                "-".repeat(*width)
+            }
+        });
+        write_row(&mut self.writer, separators.collect())?;
+        for record in records {
+            let cells = record.iter().enumerate().map(|(col, cell)| pad(cell, col));
+            write_row(&mut self.writer, cells.collect())?;
+        }
+        Ok(())
+    }
+
+    fn write_html(&mut self, right_aligned: &[bool]) -> io::Result<()> {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        };
+        let cell = |tag: &str, col: usize, text: &str| {
+            let style = if right_aligned[col] {
+                r#" style="text-align: right""#
+            } else {
+                ""
+            };
+            format!("<{}{}>{}</{}>", tag, style, escape(text), tag)
+        };
+        let w = &mut self.writer;
+        writeln!(w, "<!DOCTYPE html>")?;
+        writeln!(w, "<html>")?;
+        writeln!(w, "<head>")?;
+        writeln!(w, r#"<meta charset="utf-8">"#)?;
+        writeln!(w, "</head>")?;
+        writeln!(w, "<body>")?;
+        writeln!(w, "<table>")?;
+        writeln!(w, "<thead>")?;
+        let headers = self.headers.iter().enumerate();
+        let headers = headers.map(|(col, h)| cell("th", col, h)).join("");
+        writeln!(w, "<tr>{}</tr>", headers)?;
+        writeln!(w, "</thead>")?;
+        writeln!(w, "<tbody>")?;
+        for record in self.records.iter() {
+            let cells = record.iter().enumerate();
+            let cells = cells
+                .map(|(col, v)| cell("td", col, &v.to_string()))
+                .join("");
+            writeln!(w, "<tr>{}</tr>", cells)?;
+        }
+        writeln!(w, "</tbody>")?;
+        writeln!(w, "</table>")?;
+        writeln!(w, "</body>")?;
+        writeln!(w, "</html>")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Sink for TableSink<W> {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        self.records.push(record.to_vec());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let right_aligned = (0..self.headers.len())
+            .map(|col| {
+                let values = self.records.iter().map(|record| &record[col]);
+                let mut values = values.filter(|v| **v != Value::Null).peekable();
+                values.peek().is_some()
+                    && values.all(|v| matches!(v, Value::Integer(_) | Value::Float(_)))
+            })
+            .collect_vec();
+        match self.format {
+            OutputFormat::Html => self.write_html(&right_aligned),
+            _ => self.write_markdown(&right_aligned),
+        }
+        .context("write table")?;
+        self.writer.flush().context("flush output")
+    }
+}
+
+/// Sink inserting records into a table in a SQLite database within a transaction
+struct SqliteSink {
+    connection: Connection,
+    insert: String,
+}
+
+impl SqliteSink {
+    fn open(
+        path: &Path,
+        table: &str,
+        headers: &[String],
+        types: &[Option<ValueType>],
+        append: bool,
+    ) -> Result<Self> {
+        let connection = Connection::open(path).context("open database")?;
+        connection.execute_batch("BEGIN")?;
+        let columns: Vec<String> = connection
+            .prepare(&format!("PRAGMA table_info({})", quote(table)))?
+            .query_map([], |row| row.get("name"))?
+            .collect::<rusqlite::Result<_>>()
+            .context("read table columns")?;
+        if columns.is_empty() {
+            let mut definitions = headers.iter().zip(types).map(|(header, ty)| {
+                let ty = match ty {
+                    None | Some(ValueType::Text) => "TEXT",
+                    Some(ValueType::Integer) | Some(ValueType::Boolean) => "INTEGER",
+                    Some(ValueType::Float) => "REAL",
+                };
+                format!("{} {}", quote(header), ty)
+            });
+            let create = format!("CREATE TABLE {} ({})", quote(table), definitions.join(", "));
+            connection.execute(&create, []).context("create table")?;
+        } else {
+            ensure!(
+                append,
+                "table `{}` already exists, use --append to add to it",
+                table
+            );
+            for header in headers {
+                ensure!(
+                    columns.contains(header),
+                    "table `{}` doesn't have column `{}`",
+                    table,
+                    header,
+                );
+            }
+        }
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(table),
+            headers.iter().map(|header| quote(header)).join(", "),
+            headers.iter().map(|_| "?").join(", "),
+        );
+        Ok(SqliteSink { connection, insert })
+    }
+}
+
+impl Sink for SqliteSink {
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        use rusqlite::types::Value as SqlValue;
+        let values = record.iter().map(|value| match value {
+            Value::Null => SqlValue::Null,
+            Value::Text(s) => SqlValue::Text(s.clone()),
+            Value::Integer(i) => SqlValue::Integer(*i),
+            Value::Float(x) => SqlValue::Real(*x),
+            Value::Boolean(b) => SqlValue::Integer(*b as i64),
+        });
+        let mut statement = self.connection.prepare_cached(&self.insert)?;
+        statement
+            .execute(params_from_iter(values))
+            .context("insert record")?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.connection.execute_batch("COMMIT").context("commit")
+    }
+}
+
+/// Quote an identifier in SQL
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A record serialized as an object keyed by header in the order of the columns
+struct Row<'a>(&'a [String], &'a [Value]);
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (header, value) in self.0.iter().zip(self.1) {
+            map.serialize_entry(header, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv_transformer::CsvData;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_json() {
+        let headers = vec!["Name".to_string(), "Age".to_string(), "Rust?".to_string()];
+        let records = [
+            vec!["Alice".into(), Value::Integer(30), Value::Boolean(true)],
+            vec!["Bob".into(), Value::Null, Value::Boolean(false)],
+        ];
+        let write = |format| {
+            let buffer = Buffer::default();
+            let mut sink = create(format, headers.clone(), vec![None; 2], buffer.clone()).unwrap();
+            for record in records.iter() {
+                sink.write_record(record).unwrap();
+            }
+            sink.finish().unwrap();
+            let output = buffer.0.lock().unwrap().clone();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            write(OutputFormat::Json),
+            concat!(
+                "[\n",
+                r#"{"Name":"Alice","Age":30,"Rust?":true},"#,
+                "\n",
+                r#"{"Name":"Bob","Age":null,"Rust?":false}"#,
+                "\n]\n",
+            ),
+        );
+        assert_eq!(
+            write(OutputFormat::Ndjson),
+            concat!(
+                r#"{"Name":"Alice","Age":30,"Rust?":true}"#,
+                "\n",
+                r#"{"Name":"Bob","Age":null,"Rust?":false}"#,
+                "\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn write_xlsx() {
+        let headers = vec!["ID".to_string(), "Score".to_string()];
+        let buffer = Buffer::default();
+        let mut sink = create(OutputFormat::Xlsx, headers, vec![None; 2], buffer.clone()).unwrap();
+        sink.write_record(&["007".into(), Value::Float(4.5)])
+            .unwrap();
+        sink.write_record(&["010".into(), Value::Null]).unwrap();
+        sink.finish().unwrap();
+
+        let content = buffer.0.lock().unwrap().clone();
+        let data = CsvData::from_workbook(io::Cursor::new(&content[..]), None).unwrap();
+        assert_eq!(data.headers, ["ID", "Score"]);
+        assert_eq!(data.values, [["007", "4.5"], ["010", ""]]);
+    }
+
+    #[test]
+    fn write_sqlite() {
+        let name = format!("csv-transformer-write-sqlite.{}.db", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        let headers = vec!["Name".to_string(), "Age".to_string()];
+        let types = vec![None, Some(ValueType::Integer)];
+        let output = |append| Output {
+            output: Some(path.clone()),
+            format: None,
+            table: Some("people".to_string()),
+            append,
+            limit: None,
+        };
+        let write = |append, record: Vec<Value>| -> Result<()> {
+            let mut sink = output(append).open(headers.clone(), types.clone())?;
+            sink.write_record(&record)?;
+            sink.finish()
+        };
+        write(false, vec!["Alice".into(), Value::Integer(30)]).unwrap();
+        let error = write(false, vec!["Bob".into(), Value::Null]).err().unwrap();
+        assert!(error.to_string().contains("use --append"));
+        write(true, vec!["Bob".into(), Value::Null]).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let sql: String = connection
+            .query_row("SELECT sql FROM sqlite_master", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sql, r#"CREATE TABLE "people" ("Name" TEXT, "Age" INTEGER)"#);
+        let rows = connection
+            .prepare("SELECT Name, Age FROM people")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<(String, Option<i64>)>>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            rows,
+            [("Alice".to_string(), Some(30)), ("Bob".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn write_table() {
+        let headers = vec!["Name".to_string(), "Score".to_string()];
+        let records = [
+            vec!["A|B".into(), Value::Integer(10)],
+            vec!["<C>\nD".into(), Value::Null],
+        ];
+        let write = |format| {
+            let buffer = Buffer::default();
+            let mut sink = create(format, headers.clone(), vec![None; 2], buffer.clone()).unwrap();
+            for record in records.iter() {
+                sink.write_record(record).unwrap();
+            }
+            sink.finish().unwrap();
+            let output = buffer.0.lock().unwrap().clone();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            write(OutputFormat::Markdown),
+            concat!(
+                "| Name     | Score |\n",
+                "| -------- | ----: |\n",
+                "| A\\|B     |    10 |\n",
+                "| <C><br>D |       |\n",
+            ),
+        );
+        let html = write(OutputFormat::Html);
+        assert!(html.contains(r#"<tr><th>Name</th><th style="text-align: right">Score</th></tr>"#));
+        assert!(
+            html.contains("<tr><td>&lt;C&gt;<br>D</td><td style=\"text-align: right\"></td></tr>")
+        );
+    }
+
+    /// Examples of transformations in README should match the actual result
+    #[test]
+    fn readme_examples() {
+        use csv_transformer::Transformation;
+
+        let readme = include_str!("../../README.md");
+        let render = |headers: Vec<String>, types, records: Vec<Vec<Value>>| {
+            let buffer = Buffer::default();
+            let mut sink = create(OutputFormat::Markdown, headers, types, buffer.clone()).unwrap();
+            for record in records.iter() {
+                sink.write_record(record).unwrap();
+            }
+            sink.finish().unwrap();
+            let table = buffer.0.lock().unwrap().clone();
+            String::from_utf8(table).unwrap()
+        };
+        let parse_table = |table: &str| {
+            let mut rows = table.lines().map(|line| {
+                let line = line.trim_start_matches("| ").trim_end_matches(" |");
+                line.split(" | ").map(|cell| cell.trim_end().to_string())
+            });
+            let headers = rows.next().unwrap().collect_vec();
+            let records = rows.skip(1).map(|row| row.collect_vec()).collect_vec();
+            (headers, records)
+        };
+
+        // Files used by the examples, e.g. of lookup
+        let dir =
+            std::env::temp_dir().join(format!("csv-transformer-readme.{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut files = vec![];
+        for example in readme.split("Example with `").skip(1) {
+            if let Some((name, rest)) = example.split_once("` being\n```csv\n") {
+                let (content, _) = rest.split_once("```").unwrap();
+                std::fs::write(dir.join(name), content).unwrap();
+                files.push(name);
+            }
+        }
+
+        let mut count = 0;
+        for example in readme.split("```yaml\n").skip(1) {
+            let (yaml, rest) = match example.split_once("```\ntransforms\n\n") {
+                Some(example) => example,
+                None => continue,
+            };
+            let (input, rest) = rest.split_once("\nto\n\n").unwrap();
+            let output = &rest[..rest.find("\n\n").unwrap() + 1];
+            let (headers, records) = parse_table(input);
+            let types = vec![None; headers.len()];
+            let values = records
+                .iter()
+                .map(|r| r.iter().map(|v| v.as_str().into()).collect());
+            assert_eq!(render(headers.clone(), types, values.collect()), input);
+
+            let mut yaml = yaml.to_string();
+            for name in files.iter() {
+                let path = dir.join(name);
+                yaml = yaml.replace(name, &path.display().to_string());
+            }
+            let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+            transformation.validate(&headers).unwrap();
+            let headers = transformation.headers();
+            let headers = headers.into_iter().map(str::to_string).collect();
+            let types = transformation.column_types();
+            let records = transformation.apply_records(&records);
+            let records = records.collect::<Result<Vec<_>>>().unwrap();
+            assert_eq!(render(headers, types, records), output, "{}", yaml);
+            count += 1;
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(count, 8);
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use csv_transformer::{ColumnRef, CsvData};
+use itertools::Itertools;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Formats tried in order when guessing the format of timestamps
+const TIMESTAMP_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y/%m/%d %H:%M:%S",
+    "%d/%m/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M:%S",
+    "%d-%b-%Y %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%d/%m/%Y %H:%M",
+    "%m/%d/%Y %H:%M",
+];
+
+/// Formats tried in order when guessing the format of dates
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y", "%m/%d/%Y", "%d-%b-%Y"];
+
+/// Statistics of values in a column
+#[derive(Serialize)]
+pub struct ColumnProfile<'a> {
+    column: ColumnRef,
+    empty: usize,
+    non_empty: usize,
+    distinct: usize,
+    /// Most frequent non-empty values in descending order of their counts
+    top_values: Vec<ValueCount<'a>>,
+    /// Minimum number of characters of non-empty values
+    min_length: Option<usize>,
+    /// Maximum number of characters of non-empty values
+    max_length: Option<usize>,
+    #[serde(flatten)]
+    inferred: InferredType,
+}
+
+#[derive(Serialize)]
+pub struct ValueCount<'a> {
+    value: &'a str,
+    count: usize,
+}
+
+/// Type of values in a column inferred from all its non-empty values
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "kebab-case")]
+pub enum InferredType {
+    Empty,
+    Integer,
+    Float,
+    Timestamp { format: &'static str },
+    Date { format: &'static str },
+    Text,
+}
+
+pub fn profile(data: &CsvData, top: usize) -> Vec<ColumnProfile<'_>> {
+    data.headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| {
+            let values = data
+                .values
+                .iter()
+                .map(|record| record[index].as_str())
+                .filter(|value| !value.trim().is_empty())
+                .collect_vec();
+            let mut counts = HashMap::new();
+            for (i, value) in values.iter().enumerate() {
+                counts.entry(*value).or_insert((i, 0)).1 += 1;
+            }
+            let top_values = counts
+                .iter()
+                .sorted_by_key(|(_, (first, count))| (Reverse(*count), *first))
+                .take(top)
+                .map(|(value, (_, count))| ValueCount {
+                    value,
+                    count: *count,
+                })
+                .collect();
+            let lengths = values.iter().map(|value| value.chars().count());
+            ColumnProfile {
+                column: ColumnRef {
+                    index,
+                    header: header.clone(),
+                },
+                empty: data.values.len() - values.len(),
+                non_empty: values.len(),
+                distinct: counts.len(),
+                top_values,
+                min_length: lengths.clone().min(),
+                max_length: lengths.max(),
+                inferred: infer_type(&values),
+            }
+        })
+        .collect()
+}
+
+/// Write the column references of the profiled columns as a transformation,
+/// with comments showing samples and statistics of their values
+pub fn write_annotated_columns(
+    profiles: &[ColumnProfile],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    // JSON strings are also valid YAML double-quoted scalars.
+    let quote = |s: &str| serde_json::to_string(s).unwrap();
+    writeln!(writer, "---")?;
+    for profile in profiles.iter() {
+        writeln!(writer, "# distinct values: {}", profile.distinct)?;
+        if !profile.top_values.is_empty() {
+            let samples = profile.top_values.iter().map(|v| quote(v.value)).join(", ");
+            writeln!(writer, "# samples: {}", samples)?;
+        }
+        match &profile.inferred {
+            InferredType::Timestamp { format } | InferredType::Date { format } => {
+                writeln!(writer, "# timestamp format: {}", quote(format))?
+            }
+            _ => {}
+        }
+        writeln!(writer, "- {}", quote(&profile.column.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Infer the type of the given non-empty values
+pub fn infer_type(values: &[&str]) -> InferredType {
+    let values = values.iter().map(|value| value.trim()).collect_vec();
+    if values.is_empty() {
+        InferredType::Empty
+    } else if values.iter().all(|value| value.parse::<i64>().is_ok()) {
+        InferredType::Integer
+    } else if values.iter().all(|value| is_finite_float(value)) {
+        InferredType::Float
+    } else if let Some(format) = guess_format(&values, TIMESTAMP_FORMATS, |value, format| {
+        NaiveDateTime::parse_from_str(value, format).is_ok()
+    }) {
+        InferredType::Timestamp { format }
+    } else if let Some(format) = guess_format(&values, DATE_FORMATS, |value, format| {
+        NaiveDate::parse_from_str(value, format).is_ok()
+    }) {
+        InferredType::Date { format }
+    } else {
+        InferredType::Text
+    }
+}
+
+/// Whether the value is a finite number, where `nan` and `inf` are taken as text
+fn is_finite_float(value: &str) -> bool {
+    matches!(value.parse::<f64>(), Ok(number) if number.is_finite())
+}
+
+fn guess_format(
+    values: &[&str],
+    formats: &[&'static str],
+    is_match: impl Fn(&str, &str) -> bool,
+) -> Option<&'static str> {
+    formats
+        .iter()
+        .copied()
+        .find(|format| values.iter().all(|value| is_match(value, format)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv_transformer::transform::TransformedColumns;
+
+    #[test]
+    fn check_infer_type() {
+        assert_eq!(infer_type(&[]), InferredType::Empty);
+        assert_eq!(infer_type(&["1", " -2 "]), InferredType::Integer);
+        assert_eq!(infer_type(&["1", "2.5"]), InferredType::Float);
+        assert_eq!(infer_type(&["1", "NaN"]), InferredType::Text);
+        assert_eq!(infer_type(&["inf"]), InferredType::Text);
+        assert_eq!(
+            infer_type(&["26-Sep-2020 01:12:42", "25-Sep-2020 23:23:52"]),
+            InferredType::Timestamp {
+                format: "%d-%b-%Y %H:%M:%S"
+            },
+        );
+        assert_eq!(
+            infer_type(&["26/09/2020", "13/10/2020"]),
+            InferredType::Date { format: "%d/%m/%Y" },
+        );
+        assert_eq!(infer_type(&["1", "Rust"]), InferredType::Text);
+    }
+
+    #[test]
+    fn annotated_columns_round_trip() {
+        let data = CsvData {
+            headers: vec!["Timestamp".to_string(), "Comment: \"Why?\"".to_string()],
+            values: vec![
+                vec![
+                    "2020-09-26".to_string(),
+                    "# not a comment\nreally".to_string(),
+                ],
+                vec!["2020-09-25".to_string(), String::new()],
+            ],
+        };
+        let mut output = vec![];
+        write_annotated_columns(&profile(&data, 3), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("# timestamp format: \"%Y-%m-%d\"\n"));
+        let columns: Vec<TransformedColumns> = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(columns.len(), 2);
+    }
+}
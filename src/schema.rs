@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use csv_transformer::ColumnRef;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Read the expected headers from a YAML list of headers,
+/// or the header row of a CSV file, e.g. the result of the global survey
+pub fn read_headers(path: &Path) -> Result<Vec<String>> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if let Some("yaml") | Some("yml") = extension {
+        let file = File::open(path).context("open schema file")?;
+        return serde_yaml::from_reader(BufReader::new(file)).context("parse schema file");
+    }
+    let mut reader = csv::Reader::from_path(path).context("open schema file")?;
+    let headers = reader.headers().context("read schema headers")?;
+    Ok(headers.iter().map(|header| header.to_string()).collect())
+}
+
+/// Find the differences of the headers from the expected ones,
+/// including missing, extra and misordered columns
+pub fn diff(expected: &[String], actual: &[&str]) -> Vec<String> {
+    let column = |index, header: &str| ColumnRef {
+        index,
+        header: header.to_string(),
+    };
+    let mut differences = vec![];
+
+    // Match each column with the expected one with the same header in order,
+    // so that duplicate headers are handled
+    let mut matched = vec![None; actual.len()];
+    let mut used = vec![false; expected.len()];
+    for (i, header) in actual.iter().enumerate() {
+        let found = (expected.iter().zip(used.iter()))
+            .position(|(expected, used)| expected == header && !used);
+        if let Some(j) = found {
+            used[j] = true;
+            matched[i] = Some(j);
+        }
+    }
+    for (j, header) in expected.iter().enumerate() {
+        if !used[j] {
+            differences.push(format!("missing column `{}`", column(j, header)));
+        }
+    }
+    for (i, header) in actual.iter().enumerate() {
+        if matched[i].is_none() {
+            differences.push(format!("extra column `{}`", column(i, header)));
+        }
+    }
+
+    // Columns out of the longest run in the expected order are misordered
+    let pairs = matched.iter().enumerate();
+    let pairs: Vec<_> = pairs.filter_map(|(i, j)| j.map(|j| (i, j))).collect();
+    let in_order = longest_in_order(&pairs);
+    for ((i, j), in_order) in pairs.into_iter().zip(in_order) {
+        if !in_order {
+            differences.push(format!(
+                "misordered column `{}`, expected `{}`",
+                column(i, actual[i]),
+                column(j, &expected[j]),
+            ));
+        }
+    }
+    differences
+}
+
+/// Find the longest subsequence of the pairs of actual and expected indices
+/// which is also in the expected order, and return whether each pair is in it
+fn longest_in_order(pairs: &[(usize, usize)]) -> Vec<bool> {
+    // Length of the longest subsequence ending at each pair, and the previous pair in it
+    let mut lengths = vec![1; pairs.len()];
+    let mut previous = vec![None; pairs.len()];
+    for a in 0..pairs.len() {
+        for b in 0..a {
+            if pairs[b].1 < pairs[a].1 && lengths[b] + 1 > lengths[a] {
+                lengths[a] = lengths[b] + 1;
+                previous[a] = Some(b);
+            }
+        }
+    }
+    let mut result = vec![false; pairs.len()];
+    let last = (0..pairs.len()).max_by_key(|a| (lengths[*a], std::cmp::Reverse(*a)));
+    let mut current = last;
+    while let Some(a) = current {
+        result[a] = true;
+        current = previous[a];
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_headers() {
+        let expected = ["Q1", "Q2", "Q3", "Q4", "Q5"];
+        let expected = expected.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+        assert!(diff(&expected, &["Q1", "Q2", "Q3", "Q4", "Q5"]).is_empty());
+        assert_eq!(
+            diff(&expected, &["Q2", "Q1", "Q3", "Q4", "Q5"]),
+            ["misordered column `B: Q1`, expected `A: Q1`"],
+        );
+        assert_eq!(
+            diff(&expected, &["Q1", "Q4", "Q2", "Q3", "Extra"]),
+            [
+                "missing column `E: Q5`",
+                "extra column `E: Extra`",
+                "misordered column `B: Q4`, expected `D: Q4`",
+            ],
+        );
+    }
+}
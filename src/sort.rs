@@ -0,0 +1,265 @@
+use crate::column_ref::ColumnRef;
+use crate::transform::parse_date_time;
+use crate::value::Value;
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+/// Format of the normalized date in sort keys, which sorts in the same order as the dates
+const DATE_KEY_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// A column to sort the result by
+pub struct SortKey {
+    column: SortColumn,
+    order: Order,
+    comparison: Comparison,
+}
+
+/// Column holding the values to sort by
+pub enum SortColumn {
+    /// Column in the output of the transformation
+    Output(ColumnRef),
+    /// Column in the original data
+    Source(ColumnRef),
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Order {
+    Ascending,
+    Descending,
+}
+
+/// How values are compared
+pub enum Comparison {
+    /// Compare the text as is
+    String,
+    /// Compare as numbers
+    Numeric,
+    /// Compare the text with numbers in it compared as numbers, e.g. `Q2` before `Q10`
+    Natural,
+    /// Compare as dates parsed with the format in syntax of chrono's strftime
+    Date(String),
+}
+
+impl SortKey {
+    pub fn new(column: SortColumn, order: Order, comparison: Comparison) -> Self {
+        SortKey {
+            column,
+            order,
+            comparison,
+        }
+    }
+
+    pub fn column(&self) -> &SortColumn {
+        &self.column
+    }
+
+    /// Value of the key for the original record and its output
+    ///
+    /// Dates are normalized into a form which can be compared as text.
+    pub fn value(&self, record: &[String], output: &[Value]) -> Result<String> {
+        let value = match &self.column {
+            SortColumn::Output(column) => output[column.index].to_string(),
+            SortColumn::Source(column) => match record.get(column.index) {
+                Some(value) => value.clone(),
+                None => bail!("record has only {} columns", record.len()),
+            },
+        };
+        if value.trim().is_empty() {
+            return Ok(String::new());
+        }
+        Ok(match &self.comparison {
+            Comparison::String | Comparison::Natural => value,
+            Comparison::Numeric => {
+                let is_valid = matches!(value.trim().parse::<f64>(), Ok(n) if n.is_finite());
+                ensure!(is_valid, "`{}` is not a valid number", value);
+                value
+            }
+            Comparison::Date(format) => parse_date_time(&value, format)
+                .with_context(|| format!("parse date: {}", value))?
+                .format(DATE_KEY_FORMAT)
+                .to_string(),
+        })
+    }
+
+    /// Compare two values of the key, where empty values come first in ascending order
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        let ordering = match (a.is_empty(), b.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => match self.comparison {
+                Comparison::String | Comparison::Date(_) => a.cmp(b),
+                Comparison::Numeric => {
+                    let (a, b) = (a.trim().parse::<f64>(), b.trim().parse::<f64>());
+                    let (a, b) = (a.unwrap_or(f64::NAN), b.unwrap_or(f64::NAN));
+                    a.total_cmp(&b)
+                }
+                Comparison::Natural => natural_cmp(a, b),
+            },
+        };
+        match self.order {
+            Order::Ascending => ordering,
+            Order::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// Compare the text piece by piece, where runs of digits are compared by their numeric values
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (x, y) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y),
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (x, rest_a) = split_digits(a);
+            let (y, rest_b) = split_digits(b);
+            let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+            let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            a = rest_a;
+            b = rest_b;
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+mod de {
+    use super::{Comparison, Order, SortColumn, SortKey};
+    use crate::column_ref::ColumnRef;
+    use serde::de::value::MapAccessDeserializer;
+    use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
+    use serde::{Deserialize, Deserializer};
+    use std::fmt;
+
+    #[derive(Deserialize)]
+    struct SortKeyInternal {
+        /// Column in the output
+        column: Option<ColumnRef>,
+        /// Column in the original data
+        source: Option<ColumnRef>,
+        order: Option<Order>,
+        compare: Option<ComparisonKind>,
+        /// Format of dates
+        format: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    enum ComparisonKind {
+        String,
+        Numeric,
+        Natural,
+        Date,
+    }
+
+    impl<'de> Deserialize<'de> for SortKey {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(SortKeyVisitor)
+        }
+    }
+
+    struct SortKeyVisitor;
+
+    impl<'de> Visitor<'de> for SortKeyVisitor {
+        type Value = SortKey;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("column reference string or sort key object")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let column = ColumnRef::deserialize(v.into_deserializer())?;
+            let column = SortColumn::Output(column);
+            Ok(SortKey::new(column, Order::Ascending, Comparison::String))
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let key = SortKeyInternal::deserialize(MapAccessDeserializer::new(map))?;
+            let column = match (key.column, key.source) {
+                (Some(column), None) => SortColumn::Output(column),
+                (None, Some(column)) => SortColumn::Source(column),
+                _ => {
+                    return Err(de::Error::custom(
+                        "sort key should have either `column` or `source`",
+                    ))
+                }
+            };
+            let comparison = match (key.compare, key.format) {
+                (Some(ComparisonKind::Date), Some(format)) => Comparison::Date(format),
+                (Some(ComparisonKind::Date), None) => {
+                    return Err(de::Error::missing_field("format"));
+                }
+                (_, Some(_)) => {
+                    return Err(de::Error::custom(
+                        "`format` can only be used with `compare: date`",
+                    ))
+                }
+                (None, None) | (Some(ComparisonKind::String), None) => Comparison::String,
+                (Some(ComparisonKind::Numeric), None) => Comparison::Numeric,
+                (Some(ComparisonKind::Natural), None) => Comparison::Natural,
+            };
+            let order = key.order.unwrap_or(Order::Ascending);
+            Ok(SortKey::new(column, order, comparison))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_values() {
+        assert_eq!(natural_cmp("Q2", "Q10"), Ordering::Less);
+        assert_eq!(natural_cmp("Q02", "Q2"), Ordering::Equal);
+        assert_eq!(natural_cmp("Q2a", "Q2b"), Ordering::Less);
+        assert_eq!(natural_cmp("Q2", "Q2a"), Ordering::Less);
+
+        let column = || {
+            SortColumn::Source(ColumnRef {
+                index: 0,
+                header: "Date".to_string(),
+            })
+        };
+        let key = SortKey::new(column(), Order::Descending, Comparison::Numeric);
+        assert_eq!(key.compare("9", "10"), Ordering::Greater);
+        assert_eq!(key.compare("", "10"), Ordering::Greater);
+        assert!(key.value(&["NaN".to_string()], &[]).is_err());
+        assert!(key.value(&["inf".to_string()], &[]).is_err());
+        assert_eq!(key.value(&[" 2.5".to_string()], &[]).unwrap(), " 2.5");
+
+        let comparison = Comparison::Date("%d/%m/%Y".to_string());
+        let key = SortKey::new(column(), Order::Ascending, comparison);
+        let value = |date: &str| key.value(&[date.to_string()], &[]).unwrap();
+        let (a, b) = (value("26/09/2020"), value("25/10/2020"));
+        assert_eq!(key.compare(&a, &b), Ordering::Less);
+        assert!(key.value(&["2020-09-26".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{Context, Result};
+use csv_transformer::{Transformation, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicUsize};
+use std::{env, process};
+
+/// Number of records to sort in memory before spilling them into a temporary file
+const RUN_SIZE: usize = 100_000;
+
+/// Number for the temporary file of the next run in this process
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Sort the output records by the sort keys of the transformation
+///
+/// Records are sorted in runs of a limited size in memory, and each full run is
+/// written into a temporary file, which are merged when all the records are added.
+/// Records with the same keys stay in their original order.
+pub struct Sorter<'a> {
+    transformation: &'a Transformation,
+    /// Number of records in each run
+    run_size: usize,
+    buffer: Vec<(Vec<String>, Vec<Value>)>,
+    runs: Vec<Run>,
+}
+
+/// Temporary file holding sorted records, one JSON array of keys and values in each line,
+/// which is removed when dropped
+struct Run {
+    path: PathBuf,
+}
+
+impl<'a> Sorter<'a> {
+    pub fn new(transformation: &'a Transformation) -> Self {
+        Sorter {
+            transformation,
+            run_size: RUN_SIZE,
+            buffer: vec![],
+            runs: vec![],
+        }
+    }
+
+    /// Add an output record with the values of its sort keys
+    pub fn push(&mut self, key: Vec<String>, record: Vec<Value>) -> Result<()> {
+        self.buffer.push((key, record));
+        if self.buffer.len() >= self.run_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Pass the first `limit` sorted records to the given function
+    pub fn finish(mut self, limit: usize, mut f: impl FnMut(&[Value]) -> Result<()>) -> Result<()> {
+        if self.runs.is_empty() {
+            self.sort_buffer();
+            for (_, record) in self.buffer.iter().take(limit) {
+                f(record)?;
+            }
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        // Merge the runs by repeatedly taking the smallest head,
+        // preferring earlier runs to keep the original order of equal records
+        let mut readers = self
+            .runs
+            .iter()
+            .map(Run::reader)
+            .collect::<Result<Vec<_>>>()?;
+        let mut heads = readers
+            .iter_mut()
+            .map(read_entry)
+            .collect::<Result<Vec<_>>>()?;
+        for _ in 0..limit {
+            let smallest = (heads.iter().enumerate())
+                .filter_map(|(i, head)| head.as_ref().map(|(key, _)| (i, key)))
+                .min_by(|(_, a), (_, b)| self.transformation.compare_sort_keys(a, b));
+            let i = match smallest {
+                Some((i, _)) => i,
+                None => break,
+            };
+            let next = read_entry(&mut readers[i])?;
+            let (_, record) = mem::replace(&mut heads[i], next).unwrap();
+            f(&record)?;
+        }
+        Ok(())
+    }
+
+    fn sort_buffer(&mut self) {
+        let transformation = self.transformation;
+        self.buffer
+            .sort_by(|(a, _), (b, _)| transformation.compare_sort_keys(a, b));
+    }
+
+    /// Sort the records in memory and write them into a new run
+    fn spill(&mut self) -> Result<()> {
+        self.sort_buffer();
+        // Never reuse an existing file, which may be another run or a symlink planted there
+        let (path, file) = loop {
+            let id = NEXT_RUN.fetch_add(1, atomic::Ordering::Relaxed);
+            let name = format!("csv-transformer.{}.{}.run", process::id(), id);
+            let path = env::temp_dir().join(name);
+            let file = OpenOptions::new().write(true).create_new(true).open(&path);
+            if matches!(&file, Err(e) if e.kind() == io::ErrorKind::AlreadyExists) {
+                continue;
+            }
+            let file = file.with_context(|| format!("create temporary file {}", path.display()))?;
+            break (path, file);
+        };
+        self.runs.push(Run { path });
+        let mut writer = BufWriter::new(file);
+        for entry in self.buffer.drain(..) {
+            serde_json::to_writer(&mut writer, &entry).context("write sorted records")?;
+            writer.write_all(b"\n").context("write sorted records")?;
+        }
+        writer.flush().context("write sorted records")?;
+        Ok(())
+    }
+}
+
+impl Run {
+    fn reader(&self) -> Result<Lines<BufReader<File>>> {
+        let file = File::open(&self.path)
+            .with_context(|| format!("open temporary file {}", self.path.display()))?;
+        Ok(BufReader::new(file).lines())
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Read the next keys and record from a run
+fn read_entry(lines: &mut Lines<BufReader<File>>) -> Result<Option<(Vec<String>, Vec<Value>)>> {
+    let line = match lines.next() {
+        Some(line) => line.context("read sorted records")?,
+        None => return Ok(None),
+    };
+    let (key, record): (Vec<String>, Vec<serde_json::Value>) =
+        serde_json::from_str(&line).context("parse sorted records")?;
+    let record = record.into_iter().map(json_to_value).collect();
+    Ok(Some((key, record)))
+}
+
+/// Convert a JSON value back into the value it was serialized from
+fn json_to_value(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::Text(s),
+        value => Value::Text(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_runs() {
+        let yaml = "rules: [\"A: N\"]\nsort:\n- column: \"A: N\"\n  compare: numeric\n";
+        let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+        let mut sorter = Sorter::new(&transformation);
+        sorter.run_size = 10;
+        // A file taking the name of the next run is left alone
+        let next = NEXT_RUN.load(atomic::Ordering::Relaxed);
+        let name = format!("csv-transformer.{}.{}.run", process::id(), next);
+        let planted = env::temp_dir().join(name);
+        fs::write(&planted, "planted").unwrap();
+        let count = 25;
+        for i in 0..count {
+            let n = (i * 7 % count) as i64;
+            sorter
+                .push(vec![n.to_string()], vec![Value::Integer(n)])
+                .unwrap();
+        }
+        assert_eq!(sorter.runs.len(), 2);
+        assert_eq!(sorter.buffer.len(), 5);
+        let mut sorted = vec![];
+        sorter
+            .finish(usize::MAX, |record| {
+                sorted.push(record[0].clone());
+                Ok(())
+            })
+            .unwrap();
+        let expected = (0..count as i64).map(Value::Integer).collect::<Vec<_>>();
+        assert_eq!(sorted, expected);
+        assert_eq!(fs::read_to_string(&planted).unwrap(), "planted");
+        fs::remove_file(&planted).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use crate::transform::{Transform, TransformedColumns};
+use crate::value::{Value, ValueType};
+use anyhow::{ensure, Result};
+use serde::Deserialize;
+
+/// A list of rules transforming the original data, or the output of the previous stage
+#[derive(Deserialize)]
+pub struct Stage {
+    /// Optional name to refer to the stage
+    name: Option<String>,
+    rules: Vec<TransformedColumns>,
+}
+
+impl Stage {
+    pub fn new(name: Option<String>, rules: Vec<TransformedColumns>) -> Self {
+        Stage { name, rules }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn rules(&self) -> &[TransformedColumns] {
+        &self.rules
+    }
+
+    /// Headers of all the columns produced by the stage
+    pub fn headers(&self) -> Vec<&str> {
+        self.rules.iter().flat_map(|rule| rule.headers()).collect()
+    }
+
+    /// Declared types of all the columns produced by the stage
+    pub fn column_types(&self) -> Vec<Option<ValueType>> {
+        let types = self.rules.iter();
+        let types = types.flat_map(|rule| vec![rule.value_type(); rule.headers().len()]);
+        types.collect()
+    }
+
+    /// Check all the column references against the headers of the input
+    pub fn validate(&self, headers: &[String]) -> Result<()> {
+        self.rules.iter().try_for_each(|rule| {
+            rule.validate(&|col| {
+                let is_valid = headers
+                    .get(col.index)
+                    .map(|header| header == &col.header)
+                    .unwrap_or(false);
+                ensure!(is_valid, "invalid column reference: {}", col);
+                Ok(())
+            })
+        })
+    }
+
+    /// Transform a record of the input, which is an error if it's missing any column read
+    pub fn apply(&self, record: &[String]) -> Result<Vec<Value>> {
+        let columns = self.rules.iter().flat_map(|rule| rule.columns());
+        if let Some(column) = columns.max_by_key(|column| column.index) {
+            ensure!(
+                column.index < record.len(),
+                "record has only {} columns, missing column {}",
+                record.len(),
+                column,
+            );
+        }
+        let mut output = vec![];
+        for rule in self.rules.iter() {
+            output.extend(rule.transform(record)?);
+        }
+        Ok(output)
+    }
+}
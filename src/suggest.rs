@@ -0,0 +1,250 @@
+use csv_transformer::{ColumnRef, CsvData};
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Maximum number of distinct values for columns to be considered categorical
+const MAX_CATEGORIES: usize = 50;
+
+/// A rule in the draft transformation
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Rule {
+    Original(ColumnRef),
+    Transform(Draft),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "transform")]
+#[serde(rename_all = "kebab-case")]
+pub enum Draft {
+    Join {
+        header: String,
+        columns: Vec<ColumnRef>,
+    },
+    Transpose {
+        #[serde(serialize_with = "ser::serialize_pair_seq")]
+        sources: Vec<(ColumnRef, String)>,
+        #[serde(serialize_with = "ser::serialize_pair_seq")]
+        columns: Vec<(String, String)>,
+    },
+    Map {
+        column: ColumnRef,
+        #[serde(serialize_with = "ser::serialize_pair_seq")]
+        values: Vec<(String, String)>,
+    },
+}
+
+/// Draft a transformation from the data
+///
+/// Consecutive columns sharing the same question stem in their headers are grouped into
+/// a join if each of them only has a single value, or a transpose if they never share a
+/// value within a record. Other categorical columns get a value map with all the observed
+/// values mapped to themselves, and the rest are kept as is.
+pub fn suggest(data: &CsvData) -> Vec<Rule> {
+    let distinct = (0..data.headers.len())
+        .map(|index| distinct_values(data, index))
+        .collect_vec();
+    let mut rules = vec![];
+    let mut index = 0;
+    while index < data.headers.len() {
+        let len = match split_header(&data.headers[index]) {
+            Some((stem, _)) => data.headers[index..]
+                .iter()
+                .take_while(|header| matches!(split_header(header), Some((s, _)) if s == stem))
+                .count(),
+            None => 1,
+        };
+        let group = index..index + len;
+        match suggest_group(data, &distinct, group.clone()) {
+            Some(draft) => rules.push(Rule::Transform(draft)),
+            None => rules.extend(group.map(|i| suggest_column(data, &distinct[i], i))),
+        }
+        index += len;
+    }
+    rules
+}
+
+fn suggest_group(data: &CsvData, distinct: &[Vec<&str>], group: Range<usize>) -> Option<Draft> {
+    if group.len() < 2 {
+        return None;
+    }
+    let (stem, _) = split_header(&data.headers[group.start])?;
+    if distinct[group.clone()]
+        .iter()
+        .all(|values| values.len() <= 1)
+    {
+        let header = stem.trim_end_matches(&[' ', '-'][..]);
+        return Some(Draft::Join {
+            header: header.to_string(),
+            columns: group.map(|index| column_ref(data, index)).collect(),
+        });
+    }
+    let values = distinct[group.clone()]
+        .iter()
+        .flatten()
+        .copied()
+        .unique()
+        .collect_vec();
+    let is_exclusive = data.values.iter().all(|record| {
+        let mut seen = HashSet::new();
+        record[group.clone()]
+            .iter()
+            .filter(|value| !value.is_empty())
+            .all(|value| seen.insert(value))
+    });
+    if values.len() > MAX_CATEGORIES || !is_exclusive {
+        return None;
+    }
+    let sources = group
+        .map(|index| {
+            let (_, option) = split_header(&data.headers[index]).unwrap();
+            (column_ref(data, index), option.trim().to_string())
+        })
+        .collect();
+    let columns = values
+        .into_iter()
+        .map(|value| (format!("{}{}", stem, value), value.to_string()))
+        .collect();
+    Some(Draft::Transpose { sources, columns })
+}
+
+fn suggest_column(data: &CsvData, distinct: &[&str], index: usize) -> Rule {
+    let non_empty = data
+        .values
+        .iter()
+        .filter(|record| !record[index].is_empty())
+        .count();
+    let column = column_ref(data, index);
+    if distinct.is_empty() || distinct.len() > MAX_CATEGORIES || distinct.len() * 2 > non_empty {
+        return Rule::Original(column);
+    }
+    let values = distinct
+        .iter()
+        .map(|value| (value.to_string(), value.to_string()))
+        .collect();
+    Rule::Transform(Draft::Map { column, values })
+}
+
+/// Distinct non-empty values of a column in the order they first appear
+fn distinct_values(data: &CsvData, index: usize) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    data.values
+        .iter()
+        .map(|record| record[index].as_str())
+        .filter(|value| !value.is_empty() && seen.insert(*value))
+        .collect()
+}
+
+fn column_ref(data: &CsvData, index: usize) -> ColumnRef {
+    ColumnRef {
+        index,
+        header: data.headers[index].clone(),
+    }
+}
+
+/// Split a header into its question stem and option,
+/// e.g. `Question? Rust` into `Question? ` and `Rust`.
+fn split_header(header: &str) -> Option<(&str, &str)> {
+    ["? ", ": ", " - "]
+        .iter()
+        .filter_map(|sep| header.find(sep).map(|pos| pos + sep.len()))
+        .min()
+        .map(|pos| header.split_at(pos))
+        .filter(|(_, option)| !option.trim().is_empty())
+}
+
+mod ser {
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize_pair_seq<S, K, V>(pairs: &[(K, V)], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Serialize,
+        V: Serialize,
+    {
+        serializer.collect_map(pairs.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(headers: &[&str], values: &[&[&str]]) -> CsvData {
+        let to_strings = |row: &[&str]| row.iter().map(|s| s.to_string()).collect_vec();
+        CsvData {
+            headers: to_strings(headers),
+            values: values.iter().map(|row| to_strings(row)).collect(),
+        }
+    }
+
+    #[test]
+    fn check_split_header() {
+        assert_eq!(split_header("Question? Rust"), Some(("Question? ", "Rust")));
+        assert_eq!(split_header("Age: 18 - 24"), Some(("Age: ", "18 - 24")));
+        assert_eq!(split_header("Question?"), None);
+        assert_eq!(split_header("Timestamp"), None);
+    }
+
+    #[test]
+    fn suggest_join() {
+        let data = data(
+            &["Question? Rust", "Question? C++"],
+            &[&["Rust", ""], &["", "C++"], &["Rust", "C++"]],
+        );
+        match suggest(&data).as_slice() {
+            [Rule::Transform(Draft::Join { header, columns })] => {
+                assert_eq!(header, "Question?");
+                assert_eq!(columns.iter().map(|c| c.index).collect_vec(), [0, 1]);
+            }
+            _ => panic!("expected a single join"),
+        }
+    }
+
+    #[test]
+    fn suggest_transpose() {
+        let data = data(
+            &["Question? 1st", "Question? 2nd"],
+            &[&["Rust", "C++"], &["C++", "Go"], &["Go", ""]],
+        );
+        match suggest(&data).as_slice() {
+            [Rule::Transform(Draft::Transpose { sources, columns })] => {
+                let sources = sources.iter().map(|(c, v)| (c.index, v.as_str()));
+                assert_eq!(sources.collect_vec(), [(0, "1st"), (1, "2nd")]);
+                let columns = columns.iter().map(|(h, v)| (h.as_str(), v.as_str()));
+                assert_eq!(
+                    columns.collect_vec(),
+                    [
+                        ("Question? Rust", "Rust"),
+                        ("Question? C++", "C++"),
+                        ("Question? Go", "Go"),
+                    ],
+                );
+            }
+            _ => panic!("expected a single transpose"),
+        }
+    }
+
+    #[test]
+    fn suggest_map() {
+        let data = data(
+            &["Country", "Comment"],
+            &[
+                &["China", "Good"],
+                &["China", "Bad"],
+                &["Japan", ""],
+                &["Japan", "Fine"],
+            ],
+        );
+        match suggest(&data).as_slice() {
+            [Rule::Transform(Draft::Map { column, values }), Rule::Original(comment)] => {
+                assert_eq!(column.index, 0);
+                assert_eq!(values.len(), 2);
+                assert_eq!(comment.index, 1);
+            }
+            _ => panic!("expected a map and an original column"),
+        }
+    }
+}
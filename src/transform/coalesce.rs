@@ -0,0 +1,42 @@
+use super::{Source, Transform};
+use crate::column_ref::ColumnRef;
+use crate::value::Value;
+use anyhow::Result;
+use serde::Deserialize;
+
+/// Take the first non-empty value from multiple columns
+#[derive(Deserialize)]
+pub struct Coalesce {
+    header: String,
+    columns: Vec<Source>,
+}
+
+impl Coalesce {
+    pub fn new(header: String, columns: Vec<Source>) -> Self {
+        Coalesce { header, columns }
+    }
+}
+
+impl Transform for Coalesce {
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.columns.iter().flat_map(|c| c.columns()).collect()
+    }
+
+    fn headers(&self) -> Vec<&str> {
+        vec![&self.header]
+    }
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.columns.iter().try_for_each(|c| c.validate(check_ref))
+    }
+
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        for column in self.columns.iter() {
+            let value = column.value(record)?;
+            if !value.trim().is_empty() {
+                return Ok(vec![value.into()]);
+            }
+        }
+        Ok(vec!["".into()])
+    }
+}
@@ -1,9 +1,8 @@
-use super::Transform;
+use super::{Source, Transform};
 use crate::column_ref::ColumnRef;
-use anyhow::{Context, Result};
+use crate::value::Value;
+use anyhow::Result;
 use serde::Deserialize;
-use std::borrow::Cow;
-use std::io::Write;
 
 /// Join multiple columns together
 #[derive(Deserialize)]
@@ -19,48 +18,74 @@ struct ValueMap(ValueMapInternal);
 
 #[derive(Deserialize)]
 struct ValueMapInternal {
-    column: ColumnRef,
+    column: Source,
     /// Format to transform the value, using `{}` for the original value
     format: Option<String>,
 }
 
+impl Join {
+    /// Create a join of the given columns, each with an optional format
+    pub fn new(
+        header: String,
+        columns: Vec<(Source, Option<String>)>,
+        sep: Option<String>,
+    ) -> Self {
+        let columns = columns
+            .into_iter()
+            .map(|(column, format)| ValueMap(ValueMapInternal { column, format }))
+            .collect();
+        Join {
+            header,
+            columns,
+            sep,
+        }
+    }
+}
+
 impl Transform for Join {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+    fn columns(&self) -> Vec<&ColumnRef> {
         self.columns
             .iter()
-            .map(|c| check_ref(&c.0.column))
+            .flat_map(|c| c.0.column.columns())
             .collect()
     }
 
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        writer.write_field(&self.header).context("write header")
+    fn headers(&self) -> Vec<&str> {
+        vec![&self.header]
     }
 
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()> {
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.columns
+            .iter()
+            .try_for_each(|c| c.0.column.validate(check_ref))
+    }
+
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
         let sep = self.sep.as_deref().unwrap_or(", ");
-        let values = self.columns.iter().filter_map(|c| {
+        let mut values = vec![];
+        for c in self.columns.iter() {
             let ValueMapInternal { column, format } = &c.0;
-            match record[column.index].trim() {
-                "" => None,
-                value => Some(if let Some(format) = &format {
-                    Cow::Owned(format.replace("{}", value))
+            let value = column.value(record)?;
+            match value.trim() {
+                "" => {}
+                value => values.push(if let Some(format) = &format {
+                    format.replace("{}", value)
                 } else {
-                    Cow::Borrowed(value)
+                    value.to_string()
                 }),
             }
-        });
-        writer
-            .write_field(itertools::join(values, sep))
-            .context("write field")
+        }
+        Ok(vec![values.join(sep).into()])
     }
 }
 
 mod de {
-    use super::{ValueMap, ValueMapInternal};
+    use super::{Source, ValueMap, ValueMapInternal};
     use crate::column_ref::ColumnRef;
     use serde::de::value::MapAccessDeserializer;
     use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
     use serde::{Deserialize, Deserializer};
+    use serde_yaml::{Mapping, Value};
     use std::fmt;
 
     impl<'de> Deserialize<'de> for ValueMap {
@@ -87,7 +112,7 @@ mod de {
         {
             let column = ColumnRef::deserialize(v.into_deserializer())?;
             Ok(ValueMapInternal {
-                column,
+                column: column.into(),
                 format: None,
             })
         }
@@ -96,7 +121,17 @@ mod de {
         where
             A: MapAccess<'de>,
         {
-            Deserialize::deserialize(MapAccessDeserializer::new(map))
+            // A transformation object can be used directly as a column without format
+            let map = Mapping::deserialize(MapAccessDeserializer::new(map))?;
+            let result = if map.contains_key(&Value::from("transform")) {
+                Source::deserialize(Value::Mapping(map)).map(|column| ValueMapInternal {
+                    column,
+                    format: None,
+                })
+            } else {
+                ValueMapInternal::deserialize(Value::Mapping(map))
+            };
+            result.map_err(de::Error::custom)
         }
     }
 }
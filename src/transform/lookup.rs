@@ -0,0 +1,220 @@
+use super::{Source, Transform};
+use crate::column_ref::ColumnRef;
+use crate::value::Value;
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::collections::hash_map::{Entry, HashMap};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Look up the value of a column in another CSV file,
+/// and take the values of some columns from the matched row
+///
+/// The file is loaded once when the transformation is created.
+#[derive(Deserialize)]
+#[serde(try_from = "LookupParams")]
+pub struct Lookup {
+    column: Source,
+    /// Headers of the columns taken from the lookup file
+    headers: Vec<String>,
+    /// Values of the taken columns for each key
+    rows: HashMap<String, Vec<String>>,
+    missing: Missing,
+}
+
+/// What to produce when a non-empty value doesn't match any key
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Missing {
+    /// Raise an error, which is the default
+    Error,
+    /// Leave all the columns empty
+    Empty,
+    /// Fill all the columns with the given value
+    Default(String),
+}
+
+/// What to do when the lookup file has multiple rows with the same key
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Duplicates {
+    /// Raise an error, which is the default
+    Error,
+    /// Use the first row with the key
+    First,
+    /// Use the last row with the key
+    Last,
+}
+
+#[derive(Deserialize)]
+struct LookupParams {
+    column: Source,
+    /// Path to the lookup file, relative to the working directory
+    /// rather than the transformation file
+    file: PathBuf,
+    /// Header of the key column in the lookup file, the first column by default
+    key: Option<String>,
+    /// Headers of the columns to take from the lookup file
+    columns: Vec<String>,
+    missing: Option<Missing>,
+    duplicates: Option<Duplicates>,
+}
+
+impl Lookup {
+    /// Create a lookup with the CSV content from the given reader
+    pub fn new(
+        column: impl Into<Source>,
+        reader: impl Read,
+        key: Option<&str>,
+        columns: Vec<String>,
+        missing: Missing,
+        duplicates: Duplicates,
+    ) -> Result<Self> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let file_headers = reader.headers().context("read headers")?.clone();
+        let find = |header: &str| {
+            let index = file_headers.iter().position(|h| h == header);
+            index.ok_or_else(|| {
+                anyhow!(
+                    "column `{}` not found, expected one of: {}",
+                    header,
+                    file_headers.iter().join(", "),
+                )
+            })
+        };
+        let key_index = match key {
+            Some(key) => find(key)?,
+            None => 0,
+        };
+        let indices = columns
+            .iter()
+            .map(|header| find(header))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut rows = HashMap::new();
+        for record in reader.into_records() {
+            let record = record.context("read record")?;
+            let key = record.get(key_index).unwrap_or_default().to_string();
+            let values = indices
+                .iter()
+                .map(|i| record.get(*i).unwrap_or_default().to_string())
+                .collect();
+            match (rows.entry(key), duplicates) {
+                (Entry::Vacant(entry), _) => {
+                    entry.insert(values);
+                }
+                (Entry::Occupied(entry), Duplicates::Error) => {
+                    bail!("duplicate key `{}`", entry.key());
+                }
+                (Entry::Occupied(_), Duplicates::First) => {}
+                (Entry::Occupied(mut entry), Duplicates::Last) => {
+                    entry.insert(values);
+                }
+            }
+        }
+        Ok(Lookup {
+            column: column.into(),
+            headers: columns,
+            rows,
+            missing,
+        })
+    }
+}
+
+impl TryFrom<LookupParams> for Lookup {
+    type Error = String;
+
+    fn try_from(params: LookupParams) -> Result<Self, Self::Error> {
+        let LookupParams {
+            column,
+            file,
+            key,
+            columns,
+            missing,
+            duplicates,
+        } = params;
+        let result = File::open(&file).map_err(Into::into).and_then(|reader| {
+            let missing = missing.unwrap_or(Missing::Error);
+            let duplicates = duplicates.unwrap_or(Duplicates::Error);
+            Lookup::new(column, reader, key.as_deref(), columns, missing, duplicates)
+        });
+        result
+            .with_context(|| format!("load lookup file {}", file.display()))
+            .map_err(|e| format!("{:#}", e))
+    }
+}
+
+impl Transform for Lookup {
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.column.columns()
+    }
+
+    fn headers(&self) -> Vec<&str> {
+        self.headers.iter().map(String::as_str).collect()
+    }
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.column.validate(check_ref)
+    }
+
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        let value = self.column.value(record)?;
+        let fill = |value: &str| vec![Value::from(value); self.headers.len()];
+        if value.is_empty() {
+            return Ok(fill(""));
+        }
+        if let Some(values) = self.rows.get(&value) {
+            return Ok(values.iter().map(|v| v.as_str().into()).collect());
+        }
+        Ok(match &self.missing {
+            Missing::Error => bail!(
+                "value `{}` from column `{}` isn't found in the lookup file",
+                value,
+                self.column.header(),
+            ),
+            Missing::Empty => fill(""),
+            Missing::Default(default) => fill(default),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_rows() {
+        let file =
+            "Country,Region,Population\nChina,Asia,1400\nFrance,Europe,67\nChina,East Asia,\n,Nowhere,\n";
+        let columns = vec!["Region".to_string()];
+        let new = |missing, duplicates| {
+            let reader = file.as_bytes();
+            let columns = columns.clone();
+            let column = ColumnRef {
+                index: 1,
+                header: "Country".to_string(),
+            };
+            Lookup::new(column, reader, None, columns, missing, duplicates)
+        };
+        let error = new(Missing::Error, Duplicates::Error).err().unwrap();
+        assert_eq!(error.to_string(), "duplicate key `China`");
+
+        let lookup = new(Missing::Error, Duplicates::First).unwrap();
+        assert_eq!(lookup.headers(), ["Region"]);
+        let record = |country: &str| vec!["1".to_string(), country.to_string()];
+        let values = lookup.transform(&record("China")).unwrap();
+        assert_eq!(values, [Value::from("Asia")]);
+        let values = lookup.transform(&record("")).unwrap();
+        assert_eq!(values, [Value::from("")]);
+        assert!(lookup.transform(&record("Japan")).is_err());
+
+        let lookup = new(Missing::Default("Other".to_string()), Duplicates::Last).unwrap();
+        let values = lookup.transform(&record("China")).unwrap();
+        assert_eq!(values, [Value::from("East Asia")]);
+        let values = lookup.transform(&record("Japan")).unwrap();
+        assert_eq!(values, [Value::from("Other")]);
+    }
+}
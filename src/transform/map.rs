@@ -0,0 +1,61 @@
+use super::{Source, Transform};
+use crate::column_ref::ColumnRef;
+use crate::value::Value;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Map each value of a column to a new value
+///
+/// It's an error if a non-empty value in the column doesn't have a mapping.
+#[derive(Deserialize)]
+pub struct Map {
+    column: Source,
+    /// Optional header, if omitted, the header of the reference column would be used
+    header: Option<String>,
+    /// Original values and the new values they map to
+    values: HashMap<String, String>,
+}
+
+impl Map {
+    pub fn new(
+        column: impl Into<Source>,
+        header: Option<String>,
+        values: HashMap<String, String>,
+    ) -> Self {
+        let column = column.into();
+        Map {
+            column,
+            header,
+            values,
+        }
+    }
+}
+
+impl Transform for Map {
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.column.columns()
+    }
+
+    fn headers(&self) -> Vec<&str> {
+        vec![self.header.as_deref().unwrap_or(self.column.header())]
+    }
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.column.validate(check_ref)
+    }
+
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        let value = self.column.value(record)?;
+        let value = match self.values.get(&value) {
+            Some(new_value) => new_value.clone(),
+            None if value.is_empty() => value,
+            None => bail!(
+                "value `{}` from column `{}` doesn't have a mapping",
+                value,
+                self.column.header(),
+            ),
+        };
+        Ok(vec![value.into()])
+    }
+}
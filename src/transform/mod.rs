@@ -1,90 +1,190 @@
 use crate::column_ref::ColumnRef;
-use anyhow::Result;
-use serde::Deserialize;
-use std::io::Write;
+use crate::value::{Value, ValueType};
+use anyhow::{Context, Result};
 
+mod coalesce;
 mod join;
+mod lookup;
+mod map;
 mod original;
+mod registry;
 mod rename;
+mod source;
 mod timestamp;
 mod transpose;
 
-#[derive(Deserialize)]
-#[serde(transparent)]
+pub use coalesce::Coalesce;
+pub use join::Join;
+pub use lookup::{Duplicates, Lookup, Missing};
+pub use map::Map;
+pub use registry::{Factory, Registry};
+pub use rename::Rename;
+pub use source::Source;
+pub(crate) use timestamp::parse_date_time;
+pub use timestamp::Timestamp;
+pub use transpose::Transpose;
+
+/// A rule in the transformation generating one or more columns
 pub struct TransformedColumns {
-    #[serde(deserialize_with = "de::deserialize_internal")]
-    internal: Internal,
+    kind: String,
+    transform: Box<dyn Transform>,
+    /// Type to convert the produced values into
+    value_type: Option<ValueType>,
 }
 
 pub trait Transform {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()>;
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()>;
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()>;
+    /// Columns in the original data this transformation reads
+    fn columns(&self) -> Vec<&ColumnRef>;
+    /// Headers of the columns this transformation produces
+    fn headers(&self) -> Vec<&str>;
+    /// Values of the produced columns for the given record
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>>;
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.columns().into_iter().try_for_each(check_ref)
+    }
+
+    /// Suspicious things in this transformation which are not errors
+    fn warnings(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl TransformedColumns {
+    /// Create a rule from a transformation with the name of its kind
+    pub fn new(kind: impl Into<String>, transform: impl Transform + 'static) -> Self {
+        TransformedColumns {
+            kind: kind.into(),
+            transform: Box::new(transform),
+            value_type: None,
+        }
+    }
+
+    /// Convert the produced values into the given type
+    pub fn with_type(mut self, value_type: ValueType) -> Self {
+        self.value_type = Some(value_type);
+        self
+    }
+
+    /// Name of the kind of the transformation
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Type of the produced values, if declared
+    pub fn value_type(&self) -> Option<ValueType> {
+        self.value_type
+    }
+}
+
+/// Keep the column as is
+impl From<ColumnRef> for TransformedColumns {
+    fn from(column: ColumnRef) -> Self {
+        TransformedColumns::new("original", original::Original(column))
+    }
 }
 
-#[derive(Deserialize)]
-#[serde(tag = "transform")]
-#[serde(rename_all = "kebab-case")]
-enum Internal {
-    #[serde(skip)]
-    Original(original::Original),
-    Rename(rename::Rename),
-    Timestamp(timestamp::Timestamp),
-    Join(join::Join),
-    Transpose(transpose::Transpose),
+impl From<Rename> for TransformedColumns {
+    fn from(rename: Rename) -> Self {
+        TransformedColumns::new("rename", rename)
+    }
+}
+
+impl From<Timestamp> for TransformedColumns {
+    fn from(timestamp: Timestamp) -> Self {
+        TransformedColumns::new("timestamp", timestamp)
+    }
+}
+
+impl From<Coalesce> for TransformedColumns {
+    fn from(coalesce: Coalesce) -> Self {
+        TransformedColumns::new("coalesce", coalesce)
+    }
+}
+
+impl From<Join> for TransformedColumns {
+    fn from(join: Join) -> Self {
+        TransformedColumns::new("join", join)
+    }
+}
+
+impl From<Map> for TransformedColumns {
+    fn from(map: Map) -> Self {
+        TransformedColumns::new("map", map)
+    }
+}
+
+impl From<Lookup> for TransformedColumns {
+    fn from(lookup: Lookup) -> Self {
+        TransformedColumns::new("lookup", lookup)
+    }
+}
+
+impl From<Transpose> for TransformedColumns {
+    fn from(transpose: Transpose) -> Self {
+        TransformedColumns::new("transpose", transpose)
+    }
 }
 
 impl Transform for TransformedColumns {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()> {
-        match &self.internal {
-            Internal::Original(o) => o.validate(check_ref),
-            Internal::Timestamp(t) => t.validate(check_ref),
-            Internal::Rename(r) => r.validate(check_ref),
-            Internal::Join(j) => j.validate(check_ref),
-            Internal::Transpose(t) => t.validate(check_ref),
-        }
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.transform.columns()
     }
 
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        match &self.internal {
-            Internal::Original(o) => o.write_headers(writer),
-            Internal::Timestamp(t) => t.write_headers(writer),
-            Internal::Rename(r) => r.write_headers(writer),
-            Internal::Join(j) => j.write_headers(writer),
-            Internal::Transpose(t) => t.write_headers(writer),
-        }
+    fn headers(&self) -> Vec<&str> {
+        self.transform.headers()
     }
 
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        match &self.internal {
-            Internal::Original(o) => o.write_fields(record, writer),
-            Internal::Timestamp(t) => t.write_fields(record, writer),
-            Internal::Rename(r) => r.write_fields(record, writer),
-            Internal::Join(j) => j.write_fields(record, writer),
-            Internal::Transpose(t) => t.write_fields(record, writer),
-        }
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        let values = self.transform.transform(record)?;
+        let value_type = match self.value_type {
+            Some(value_type) => value_type,
+            None => return Ok(values),
+        };
+        let headers = self.transform.headers();
+        values
+            .into_iter()
+            .zip(headers)
+            .map(|(value, header)| {
+                value
+                    .convert(value_type)
+                    .with_context(|| format!("convert value of column `{}`", header))
+            })
+            .collect()
+    }
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.transform.validate(check_ref)
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.transform.warnings()
     }
 }
 
 mod de {
-    use super::Internal;
+    use super::{Registry, TransformedColumns};
     use crate::column_ref::ColumnRef;
+    use crate::value::ValueType;
     use serde::de::value::MapAccessDeserializer;
     use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
     use serde::{Deserialize, Deserializer};
+    use serde_yaml::{Mapping, Value};
     use std::fmt;
 
-    pub(super) fn deserialize_internal<'de, D>(deserializer: D) -> Result<Internal, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_any(InternalVisitor)
+    impl<'de> Deserialize<'de> for TransformedColumns {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(TransformedColumnsVisitor)
+        }
     }
 
-    struct InternalVisitor;
+    struct TransformedColumnsVisitor;
 
-    impl<'de> Visitor<'de> for InternalVisitor {
-        type Value = Internal;
+    impl<'de> Visitor<'de> for TransformedColumnsVisitor {
+        type Value = TransformedColumns;
 
         fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
             formatter.write_str("column reference string or transformation object")
@@ -95,14 +195,46 @@ mod de {
             E: de::Error,
         {
             let column_ref = ColumnRef::deserialize(v.into_deserializer())?;
-            Ok(Internal::Original(super::original::Original(column_ref)))
+            Ok(column_ref.into())
         }
 
         fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
         where
             A: MapAccess<'de>,
         {
-            Deserialize::deserialize(MapAccessDeserializer::new(map))
+            let mut params = Mapping::deserialize(MapAccessDeserializer::new(map))?;
+            let kind = match params.remove(&Value::from("transform")) {
+                Some(Value::String(kind)) => Some(kind),
+                Some(_) => return Err(de::Error::custom("transform should be a string")),
+                None => None,
+            };
+            // `type` declares the type of the values for every kind of rule
+            let value_type = params.remove(&Value::from("type"));
+            let value_type = value_type
+                .map(ValueType::deserialize)
+                .transpose()
+                .map_err(de::Error::custom)?;
+            let rule = match kind {
+                Some(kind) => {
+                    let transform = Registry::with_current(|registry| {
+                        registry.build(&kind, Value::Mapping(params))
+                    })
+                    .map_err(|e| de::Error::custom(format!("{:#}", e)))?;
+                    TransformedColumns {
+                        kind,
+                        transform,
+                        value_type: None,
+                    }
+                }
+                // Keep the column as is, which is useful for declaring its type
+                None if params.len() == 1 && params.contains_key(&Value::from("column")) => {
+                    let column = params.remove(&Value::from("column")).unwrap();
+                    let column = ColumnRef::deserialize(column).map_err(de::Error::custom)?;
+                    column.into()
+                }
+                None => return Err(de::Error::missing_field("transform")),
+            };
+            Ok(TransformedColumns { value_type, ..rule })
         }
     }
 }
@@ -1,25 +1,20 @@
 use super::Transform;
 use crate::column_ref::ColumnRef;
-use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::io::Write;
+use crate::value::Value;
+use anyhow::Result;
 
-#[derive(Deserialize)]
-#[serde(transparent)]
 pub struct Original(pub(super) ColumnRef);
 
 impl Transform for Original {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()> {
-        check_ref(&self.0)
+    fn columns(&self) -> Vec<&ColumnRef> {
+        vec![&self.0]
     }
 
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        writer.write_field(&self.0.header).context("write header")
+    fn headers(&self) -> Vec<&str> {
+        vec![&self.0.header]
     }
 
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        writer
-            .write_field(&record[self.0.index])
-            .context("write field")
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        Ok(vec![record[self.0.index].as_str().into()])
     }
 }
@@ -0,0 +1,165 @@
+use super::{Coalesce, Join, Lookup, Map, Rename, Timestamp, Transform, Transpose};
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Function building a transformation from its parameters in the transformation file
+pub type Factory = fn(Value) -> Result<Box<dyn Transform>>;
+
+/// Named kinds of transformations available in the transformation file
+///
+/// The default registry contains all the built-in transformations.
+#[derive(Clone)]
+pub struct Registry {
+    factories: BTreeMap<String, Factory>,
+}
+
+thread_local! {
+    /// Registry used for deserializing transformations on this thread
+    static CURRENT: RefCell<Option<Registry>> = const { RefCell::new(None) };
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        registry.register::<Rename>("rename");
+        registry.register::<Timestamp>("timestamp");
+        registry.register::<Join>("join");
+        registry.register::<Coalesce>("coalesce");
+        registry.register::<Map>("map");
+        registry.register::<Transpose>("transpose");
+        registry.register::<Lookup>("lookup");
+        registry
+    }
+}
+
+impl Registry {
+    /// Create a registry without any transformation
+    pub fn empty() -> Self {
+        Registry {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// Register a transformation deserialized from its parameters with the given name
+    pub fn register<T>(&mut self, name: &str)
+    where
+        T: Transform + DeserializeOwned + 'static,
+    {
+        self.register_factory(name, build::<T>);
+    }
+
+    /// Register a transformation built by the given factory with the given name
+    ///
+    /// The `type` field of rules is reserved for declaring the type of the values,
+    /// so it is never passed to the factory.
+    pub fn register_factory(&mut self, name: &str, factory: Factory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// Names of all the registered transformations
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(|name| name.as_str())
+    }
+
+    pub(crate) fn build(&self, name: &str, params: Value) -> Result<Box<dyn Transform>> {
+        let factory = self.factories.get(name).ok_or_else(|| {
+            anyhow!(
+                "unknown transform `{}`, expected one of: {}",
+                name,
+                self.names().join(", "),
+            )
+        })?;
+        factory(params).with_context(|| format!("{} transform", name))
+    }
+
+    /// Run the function with this registry used for deserializing transformations
+    pub(crate) fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Registry>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+        let _restore = Restore(CURRENT.with(|current| current.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// Run the function with the registry for deserializing transformations,
+    /// which is the default registry outside any scope
+    pub(crate) fn with_current<R>(f: impl FnOnce(&Registry) -> R) -> R {
+        CURRENT.with(|current| match &*current.borrow() {
+            Some(registry) => f(registry),
+            None => f(&Registry::default()),
+        })
+    }
+}
+
+fn build<T>(params: Value) -> Result<Box<dyn Transform>>
+where
+    T: Transform + DeserializeOwned + 'static,
+{
+    Ok(Box::new(serde_yaml::from_value::<T>(params)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_ref::ColumnRef;
+    use crate::value::{Value, ValueType};
+    use crate::Transformation;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Constant {
+        header: String,
+        value: String,
+    }
+
+    impl Transform for Constant {
+        fn columns(&self) -> Vec<&ColumnRef> {
+            vec![]
+        }
+
+        fn headers(&self) -> Vec<&str> {
+            vec![&self.header]
+        }
+
+        fn transform(&self, _record: &[String]) -> Result<Vec<Value>> {
+            Ok(vec![self.value.as_str().into()])
+        }
+    }
+
+    #[test]
+    fn register_custom_transform() {
+        let yaml = "- transform: constant\n  header: Locale\n  value: zh-CN\n";
+        let error = Transformation::from_yaml(yaml.as_bytes()).err().unwrap();
+        assert!(format!("{:#}", error).contains("expected one of: coalesce, join, lookup, map,"));
+
+        let mut registry = Registry::default();
+        registry.register::<Constant>("constant");
+        let transformation = Transformation::from_yaml_with(yaml.as_bytes(), &registry).unwrap();
+        assert_eq!(transformation.stages()[0].rules()[0].kind(), "constant");
+        assert_eq!(transformation.headers(), ["Locale"]);
+        assert_eq!(transformation.apply(&[]).unwrap(), [Value::from("zh-CN")]);
+
+        // `type` declares the type of the values of custom transformations as well
+        let yaml = "- transform: constant\n  header: Count\n  value: \"3\"\n  type: integer\n";
+        let transformation = Transformation::from_yaml_with(yaml.as_bytes(), &registry).unwrap();
+        assert_eq!(transformation.column_types(), [Some(ValueType::Integer)]);
+        assert_eq!(transformation.apply(&[]).unwrap(), [Value::Integer(3)]);
+
+        let yaml = "rules:\n- transform: constant\n  header: Locale\n";
+        let error = Transformation::from_yaml_with(yaml.as_bytes(), &registry)
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "parse transformation: rules[0]: constant transform: \
+             missing field `value` at line 2 column 12",
+        );
+    }
+}
@@ -1,27 +1,47 @@
-use super::Transform;
+use super::{Source, Transform};
 use crate::column_ref::ColumnRef;
-use anyhow::{Context, Result};
+use crate::value::Value;
+use anyhow::Result;
 use serde::Deserialize;
-use std::io::Write;
 
 #[derive(Deserialize)]
 pub struct Rename {
     header: String,
-    column: ColumnRef,
+    column: Source,
+}
+
+impl Rename {
+    pub fn new(header: String, column: impl Into<Source>) -> Self {
+        let column = column.into();
+        Rename { header, column }
+    }
 }
 
 impl Transform for Rename {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()> {
-        check_ref(&self.column)
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.column.columns()
+    }
+
+    fn headers(&self) -> Vec<&str> {
+        vec![&self.header]
+    }
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.column.validate(check_ref)
     }
 
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        writer.write_field(&self.header).context("write header")
+    fn warnings(&self) -> Vec<String> {
+        if self.header == self.column.header() {
+            vec![format!(
+                "rename header is the same as the original: {}",
+                self.header
+            )]
+        } else {
+            vec![]
+        }
     }
 
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        writer
-            .write_field(&record[self.column.index])
-            .context("write field")
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        Ok(vec![self.column.value(record)?.into()])
     }
 }
@@ -0,0 +1,142 @@
+use super::{Transform, TransformedColumns};
+use crate::column_ref::ColumnRef;
+use anyhow::{ensure, Result};
+
+/// Input of a transformation, which is either a column in the original data,
+/// or the output of another transformation producing a single column
+pub struct Source(Inner);
+
+enum Inner {
+    Column(ColumnRef),
+    Transform(Box<TransformedColumns>),
+}
+
+impl Source {
+    /// Use the output of the given rule as the input, which must produce a single column
+    pub fn nested(rule: TransformedColumns) -> Result<Self> {
+        let count = rule.headers().len();
+        ensure!(
+            count == 1,
+            "nested {} transform should produce a single column, but it produces {}",
+            rule.kind(),
+            count,
+        );
+        Ok(Source(Inner::Transform(Box::new(rule))))
+    }
+
+    /// Columns in the original data this source reads
+    pub fn columns(&self) -> Vec<&ColumnRef> {
+        match &self.0 {
+            Inner::Column(column) => vec![column],
+            Inner::Transform(rule) => rule.columns(),
+        }
+    }
+
+    /// Header of the column, which can be used as the default header of the output
+    pub fn header(&self) -> &str {
+        match &self.0 {
+            Inner::Column(column) => &column.header,
+            Inner::Transform(rule) => rule.headers()[0],
+        }
+    }
+
+    pub fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        match &self.0 {
+            Inner::Column(column) => check_ref(column),
+            Inner::Transform(rule) => rule.validate(check_ref),
+        }
+    }
+
+    /// Value of the column for the given record
+    pub fn value(&self, record: &[String]) -> Result<String> {
+        match &self.0 {
+            Inner::Column(column) => Ok(record[column.index].clone()),
+            Inner::Transform(rule) => Ok(rule.transform(record)?[0].to_string()),
+        }
+    }
+}
+
+impl From<ColumnRef> for Source {
+    fn from(column: ColumnRef) -> Self {
+        Source(Inner::Column(column))
+    }
+}
+
+mod de {
+    use super::Source;
+    use crate::column_ref::ColumnRef;
+    use crate::transform::TransformedColumns;
+    use serde::de::value::MapAccessDeserializer;
+    use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
+    use serde::{Deserialize, Deserializer};
+    use std::fmt;
+
+    impl<'de> Deserialize<'de> for Source {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(SourceVisitor)
+        }
+    }
+
+    struct SourceVisitor;
+
+    impl<'de> Visitor<'de> for SourceVisitor {
+        type Value = Source;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("column reference string or transformation object")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let column_ref = ColumnRef::deserialize(v.into_deserializer())?;
+            Ok(column_ref.into())
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let rule = TransformedColumns::deserialize(MapAccessDeserializer::new(map))?;
+            Source::nested(rule).map_err(|e| de::Error::custom(format!("{:#}", e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Transformation;
+
+    #[test]
+    fn nested_transform() {
+        let yaml = r#"
+        - transform: rename
+          header: Country
+          column:
+            transform: map
+            column: "A: Country?"
+            values: { 中国: China }
+        "#;
+        let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+        let record = ["中国".to_string()];
+        assert_eq!(transformation.apply(&record).unwrap(), ["China".into()]);
+    }
+
+    #[test]
+    fn nested_transform_with_multiple_columns() {
+        let yaml = r#"
+        - transform: rename
+          header: Language
+          column:
+            transform: transpose
+            sources: { "A: Language? 1st": 1st }
+            columns: { Rust: Rust, C++: C++ }
+        "#;
+        let error = Transformation::from_yaml(yaml.as_bytes()).err().unwrap();
+        assert!(format!("{:#}", error).contains("should produce a single column"));
+    }
+}
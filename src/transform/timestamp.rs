@@ -1,14 +1,14 @@
-use super::Transform;
+use super::{Source, Transform};
 use crate::column_ref::ColumnRef;
+use crate::value::Value;
 use anyhow::{Context, Result};
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, ParseResult};
 use serde::Deserialize;
-use std::io::Write;
 
 /// Reformat the timestamp with the given spec
 #[derive(Deserialize)]
 pub struct Timestamp {
-    column: ColumnRef,
+    column: Source,
     /// Optional header, if omitted, the header of the reference column would be used
     header: Option<String>,
     /// Format to parse the timestamp in syntax of chrono's strftime
@@ -17,23 +17,73 @@ pub struct Timestamp {
     to: String,
 }
 
+impl Timestamp {
+    pub fn new(
+        column: impl Into<Source>,
+        header: Option<String>,
+        from: String,
+        to: String,
+    ) -> Self {
+        let column = column.into();
+        Timestamp {
+            column,
+            header,
+            from,
+            to,
+        }
+    }
+}
+
 impl Transform for Timestamp {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()> {
-        check_ref(&self.column)
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.column.columns()
     }
 
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        writer
-            .write_field(self.header.as_deref().unwrap_or(&self.column.header))
-            .context("write header")
+    fn headers(&self) -> Vec<&str> {
+        vec![self.header.as_deref().unwrap_or(self.column.header())]
     }
 
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        let value = &record[self.column.index];
-        let time = NaiveDateTime::parse_from_str(value, &self.from)
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.column.validate(check_ref)
+    }
+
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
+        let value = &self.column.value(record)?;
+        let time = parse_date_time(value, &self.from)
             .with_context(|| format!("parse timestamp: {}", value))?;
-        writer
-            .write_field(time.format(&self.to).to_string())
-            .context("write field")
+        Ok(vec![time.format(&self.to).to_string().into()])
+    }
+}
+
+/// Parse a timestamp in the given format, where a format with only a date gives midnight of the day
+pub(crate) fn parse_date_time(value: &str, format: &str) -> ParseResult<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, format).or_else(|e| {
+        NaiveDate::parse_from_str(value, format)
+            .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+            .map_err(|_| e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reformat_timestamp() {
+        let timestamp = |from: &str| {
+            let column = ColumnRef {
+                index: 0,
+                header: "Date".to_string(),
+            };
+            Timestamp::new(column, None, from.to_string(), "%d/%m/%Y %H:%M".to_string())
+        };
+        let record = vec!["2020-03-04".to_string()];
+        let output = timestamp("%Y-%m-%d").transform(&record).unwrap();
+        assert_eq!(output[0].to_string(), "04/03/2020 00:00");
+        let record = vec!["2020-03-04 12:30".to_string()];
+        let output = timestamp("%Y-%m-%d %H:%M").transform(&record).unwrap();
+        assert_eq!(output[0].to_string(), "04/03/2020 12:30");
+        let error = timestamp("%Y-%m-%d").transform(&record).unwrap_err();
+        assert_eq!(error.to_string(), "parse timestamp: 2020-03-04 12:30");
     }
 }
@@ -1,9 +1,9 @@
 use super::Transform;
 use crate::column_ref::ColumnRef;
-use anyhow::{ensure, Context, Result};
+use crate::value::Value;
+use anyhow::{ensure, Result};
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::io::Write;
 
 /// Transpose values and their header across several columns
 ///
@@ -34,12 +34,19 @@ pub struct Transpose {
     columns: Vec<(String, String)>,
 }
 
+impl Transpose {
+    pub fn new(sources: Vec<(ColumnRef, String)>, columns: Vec<(String, String)>) -> Self {
+        Transpose { sources, columns }
+    }
+}
+
 impl Transform for Transpose {
-    fn validate(&self, check_ref: impl Fn(&ColumnRef) -> Result<()>) -> Result<()> {
-        self.sources
-            .iter()
-            .map(|(c, _)| check_ref(c))
-            .collect::<Result<()>>()?;
+    fn columns(&self) -> Vec<&ColumnRef> {
+        self.sources.iter().map(|(c, _)| c).collect()
+    }
+
+    fn validate(&self, check_ref: &dyn Fn(&ColumnRef) -> Result<()>) -> Result<()> {
+        self.sources.iter().try_for_each(|(c, _)| check_ref(c))?;
         // Validate that each new column takes different values from the old columns.
         let mut value_to_new_column = HashMap::new();
         for (header, value) in self.columns.iter() {
@@ -50,7 +57,7 @@ impl Transform for Transpose {
             );
             value_to_new_column
                 .entry(value.as_str())
-                .or_insert_with(|| Vec::new())
+                .or_insert_with(Vec::new)
                 .push(header.as_str());
         }
         for (value, headers) in value_to_new_column.iter() {
@@ -64,14 +71,11 @@ impl Transform for Transpose {
         Ok(())
     }
 
-    fn write_headers(&self, writer: &mut csv::Writer<impl Write>) -> Result<()> {
-        self.columns
-            .iter()
-            .map(|(h, _)| writer.write_field(h).context("write field"))
-            .collect()
+    fn headers(&self) -> Vec<&str> {
+        self.columns.iter().map(|(h, _)| h.as_str()).collect()
     }
 
-    fn write_fields(&self, record: &[String], writer: &mut csv::Writer<impl Write>) -> Result<()> {
+    fn transform(&self, record: &[String]) -> Result<Vec<Value>> {
         // Verify that every value has a column to go.
         for (col, _) in self.sources.iter() {
             let value = &record[col.index];
@@ -86,15 +90,11 @@ impl Transform for Transpose {
                 col.header,
             );
         }
-        self.columns
-            .iter()
-            .map(|(_, value)| {
-                let source = self.sources.iter().find(|(c, _)| &record[c.index] == value);
-                writer
-                    .write_field(source.map_or("", |(_, v)| v.as_str()))
-                    .context("write field")
-            })
-            .collect()
+        let values = self.columns.iter().map(|(_, value)| {
+            let source = self.sources.iter().find(|(c, _)| &record[c.index] == value);
+            source.map_or("", |(_, v)| v.as_str()).into()
+        });
+        Ok(values.collect())
     }
 }
 
@@ -113,14 +113,16 @@ mod de {
         deserializer.deserialize_map(PairSeqVisitor(PhantomData))
     }
 
-    struct PairSeqVisitor<K, V>(PhantomData<fn() -> Vec<(K, V)>>);
+    type PairSeq<K, V> = Vec<(K, V)>;
+
+    struct PairSeqVisitor<K, V>(PhantomData<fn() -> PairSeq<K, V>>);
 
     impl<'de, K, V> Visitor<'de> for PairSeqVisitor<K, V>
     where
         K: Deserialize<'de>,
         V: Deserialize<'de>,
     {
-        type Value = Vec<(K, V)>;
+        type Value = PairSeq<K, V>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
             formatter.write_str("a map")
@@ -138,3 +140,28 @@ mod de {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transpose_record() {
+        let source = |index, header: &str, value: &str| {
+            let header = header.to_string();
+            (ColumnRef { index, header }, value.to_string())
+        };
+        let column = |header: &str, value: &str| (header.to_string(), value.to_string());
+        let transpose = Transpose::new(
+            vec![source(0, "Q? 1st", "1st"), source(1, "Q? 2nd", "2nd")],
+            vec![column("Q? Go", "Go"), column("Q? Rust", "Rust")],
+        );
+        let record = ["Rust".to_string(), String::new()];
+        assert_eq!(
+            transpose.transform(&record).unwrap(),
+            [Value::from(""), Value::from("1st")],
+        );
+        let record = ["Rust".to_string(), "C++".to_string()];
+        assert!(transpose.transform(&record).is_err());
+    }
+}
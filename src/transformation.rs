@@ -0,0 +1,398 @@
+use crate::aggregate::Group;
+use crate::column_ref::ColumnRef;
+use crate::sort::{SortColumn, SortKey};
+use crate::stage::Stage;
+use crate::transform::{Registry, Transform, TransformedColumns};
+use crate::value::{Value, ValueType};
+use anyhow::{bail, ensure, Context, Result};
+use std::cmp::Ordering;
+use std::io::Read;
+
+/// Rules to transform data in one or more stages
+///
+/// Each stage transforms the output of the previous stage,
+/// and the output of the last stage is the result of the transformation,
+/// optionally grouped into one record for each group, and sorted by some columns.
+pub struct Transformation {
+    stages: Vec<Stage>,
+    group: Option<Group>,
+    sort: Vec<SortKey>,
+}
+
+impl Transformation {
+    /// Create a transformation with a single stage of the given rules
+    pub fn new(rules: Vec<TransformedColumns>) -> Self {
+        Transformation {
+            stages: vec![Stage::new(None, rules)],
+            group: None,
+            sort: vec![],
+        }
+    }
+
+    pub fn from_stages(stages: Vec<Stage>) -> Result<Self> {
+        ensure!(
+            !stages.is_empty(),
+            "transformation should have at least one stage"
+        );
+        Ok(Transformation {
+            stages,
+            group: None,
+            sort: vec![],
+        })
+    }
+
+    /// Group the output of the last stage, and produce one record for each group
+    pub fn with_group(mut self, group: Group) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    /// Sort the result by the given keys, in order of precedence
+    pub fn with_sort(mut self, sort: Vec<SortKey>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Load the transformation from a YAML document with the built-in transformations
+    pub fn from_yaml(reader: impl Read) -> Result<Self> {
+        Self::from_yaml_with(reader, &Registry::default())
+    }
+
+    /// Load the transformation from a YAML document with transformations in the registry
+    pub fn from_yaml_with(reader: impl Read, registry: &Registry) -> Result<Self> {
+        let document: de::Document = registry
+            .scope(|| serde_yaml::from_reader(reader))
+            .context("parse transformation")?;
+        let mut transformation = Self::from_stages(document.stages)?.with_sort(document.sort);
+        if let Some(group) = document.group {
+            transformation = transformation.with_group(group);
+        }
+        Ok(transformation)
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Grouping of the output of the last stage, if any
+    pub fn group(&self) -> Option<&Group> {
+        self.group.as_ref()
+    }
+
+    /// Keys to sort the result by, which is empty if the result isn't sorted
+    pub fn sort(&self) -> &[SortKey] {
+        &self.sort
+    }
+
+    /// Columns of the original data read by the rules of the first stage or the sort keys
+    pub fn source_columns(&self) -> Vec<&ColumnRef> {
+        let rules = self.stages[0]
+            .rules()
+            .iter()
+            .flat_map(|rule| rule.columns());
+        let sort = self.sort.iter().filter_map(|key| match key.column() {
+            SortColumn::Source(column) => Some(column),
+            SortColumn::Output(_) => None,
+        });
+        rules.chain(sort).collect()
+    }
+
+    /// Find the stage with the given name
+    pub fn stage(&self, name: &str) -> Option<&Stage> {
+        self.stages.iter().find(|stage| stage.name() == Some(name))
+    }
+
+    /// Headers of all the columns in the result, which are those of the groups if grouped
+    pub fn headers(&self) -> Vec<&str> {
+        match &self.group {
+            Some(group) => group.headers(),
+            None => self.stages.last().unwrap().headers(),
+        }
+    }
+
+    /// Declared types of all the columns in the result
+    pub fn column_types(&self) -> Vec<Option<ValueType>> {
+        let types = self.stages.last().unwrap().column_types();
+        match &self.group {
+            Some(group) => group.column_types(&types),
+            None => types,
+        }
+    }
+
+    /// Check all the column references against the headers of the original data,
+    /// and those in each stage against the headers produced by the previous stage
+    pub fn validate(&self, headers: &[String]) -> Result<()> {
+        let original = headers;
+        let mut headers = headers.to_vec();
+        for (i, stage) in self.stages.iter().enumerate() {
+            self.stage_context(i, stage.validate(&headers))?;
+            headers = stage.headers().into_iter().map(str::to_string).collect();
+        }
+        if let Some(group) = &self.group {
+            group.validate(&headers)?;
+            headers = group.headers().into_iter().map(str::to_string).collect();
+        }
+        for key in self.sort.iter() {
+            let (column, headers) = match key.column() {
+                SortColumn::Output(column) => (column, &headers[..]),
+                SortColumn::Source(column) if self.group.is_some() => {
+                    bail!("can't sort grouped result by source column: {}", column)
+                }
+                SortColumn::Source(column) => (column, original),
+            };
+            let is_valid = headers
+                .get(column.index)
+                .map(|header| header == &column.header)
+                .unwrap_or(false);
+            ensure!(is_valid, "invalid column reference in sort: {}", column);
+        }
+        Ok(())
+    }
+
+    /// Values of the sort keys for a record in the original data and its output
+    ///
+    /// If the result is grouped, the output is the record of a group, and the original record
+    /// isn't used.
+    pub fn sort_key(&self, record: &[String], output: &[Value]) -> Result<Vec<String>> {
+        self.sort
+            .iter()
+            .map(|key| {
+                key.value(record, output)
+                    .with_context(|| match key.column() {
+                        SortColumn::Output(column) => format!("sort by `{}`", column),
+                        SortColumn::Source(column) => format!("sort by source `{}`", column),
+                    })
+            })
+            .collect()
+    }
+
+    /// Compare the values of the sort keys of two records
+    pub fn compare_sort_keys(&self, a: &[String], b: &[String]) -> Ordering {
+        let keys = self.sort.iter().zip(a.iter().zip(b.iter()));
+        keys.map(|(key, (a, b))| key.compare(a, b))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Transform a record in the original data, and return the output of every stage
+    ///
+    /// If the result is grouped, the output of the last stage should be added into the
+    /// [`Aggregator`](crate::Aggregator) of the group to produce the result.
+    ///
+    /// The transformation should have been validated against the headers of the data.
+    pub fn apply_stages(&self, record: &[String]) -> Result<Vec<Vec<Value>>> {
+        let mut outputs: Vec<Vec<Value>> = vec![];
+        for (i, stage) in self.stages.iter().enumerate() {
+            let output = match outputs.last() {
+                None => stage.apply(record),
+                Some(input) => {
+                    let input = input.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                    stage.apply(&input)
+                }
+            };
+            outputs.push(self.stage_context(i, output)?);
+        }
+        Ok(outputs)
+    }
+
+    /// Transform a record in the original data
+    ///
+    /// The transformation should have been validated against the headers of the data.
+    pub fn apply(&self, record: &[String]) -> Result<Vec<Value>> {
+        Ok(self.apply_stages(record)?.pop().unwrap())
+    }
+
+    /// Transform each of the records in the original data
+    ///
+    /// The transformation should have been validated against the headers of the data.
+    pub fn apply_records<'a, I>(
+        &'a self,
+        records: I,
+    ) -> impl Iterator<Item = Result<Vec<Value>>> + 'a
+    where
+        I: IntoIterator + 'a,
+        I::Item: AsRef<[String]>,
+    {
+        records.into_iter().enumerate().map(move |(i, record)| {
+            self.apply(record.as_ref())
+                .with_context(|| format!("transform record {}", i))
+        })
+    }
+
+    /// Validate the transformation against the headers from the reader,
+    /// then transform each of the records from it
+    pub fn apply_reader<'a, R: Read>(
+        &'a self,
+        reader: &'a mut csv::Reader<R>,
+    ) -> Result<impl Iterator<Item = Result<Vec<Value>>> + 'a> {
+        let headers = reader.headers().context("read headers")?;
+        self.validate(&headers.iter().map(|h| h.to_string()).collect::<Vec<_>>())?;
+        let records = reader.records().enumerate().map(|(i, record)| {
+            let record = record.with_context(|| format!("read record {}", i))?;
+            Ok(record.iter().map(|r| r.to_string()).collect::<Vec<_>>())
+        });
+        Ok(records.enumerate().map(move |(i, record)| {
+            record.and_then(|record: Vec<String>| {
+                self.apply(&record)
+                    .with_context(|| format!("transform record {}", i))
+            })
+        }))
+    }
+
+    /// Add the stage to the context of the error if there are multiple stages
+    fn stage_context<T>(&self, index: usize, result: Result<T>) -> Result<T> {
+        if self.stages.len() == 1 {
+            return result;
+        }
+        result.with_context(|| match self.stages[index].name() {
+            Some(name) => format!("stage `{}`", name),
+            None => format!("stage {}", index),
+        })
+    }
+}
+
+mod de {
+    use crate::aggregate::Group;
+    use crate::sort::SortKey;
+    use crate::stage::Stage;
+    use crate::transform::TransformedColumns;
+    use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+    use serde::de::{self, MapAccess, SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer};
+    use std::fmt;
+
+    /// Content of a transformation file, which can be either a list of rules as a single stage,
+    /// or an object with a list of stages or rules, and the keys to sort the result by
+    pub(super) struct Document {
+        pub(super) stages: Vec<Stage>,
+        pub(super) group: Option<Group>,
+        pub(super) sort: Vec<SortKey>,
+    }
+
+    #[derive(Deserialize)]
+    struct Pipeline {
+        stages: Option<Vec<Stage>>,
+        rules: Option<Vec<TransformedColumns>>,
+        group: Option<Group>,
+        #[serde(default)]
+        sort: Vec<SortKey>,
+    }
+
+    impl<'de> Deserialize<'de> for Document {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(DocumentVisitor)
+        }
+    }
+
+    struct DocumentVisitor;
+
+    impl<'de> Visitor<'de> for DocumentVisitor {
+        type Value = Document;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("list of rules or object with stages or rules")
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let rules = Vec::<TransformedColumns>::deserialize(SeqAccessDeserializer::new(seq))?;
+            Ok(Document {
+                stages: vec![Stage::new(None, rules)],
+                group: None,
+                sort: vec![],
+            })
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let pipeline = Pipeline::deserialize(MapAccessDeserializer::new(map))?;
+            let stages = match (pipeline.stages, pipeline.rules) {
+                (Some(stages), None) => stages,
+                (None, Some(rules)) => vec![Stage::new(None, rules)],
+                _ => return Err(de::Error::custom("expected either `stages` or `rules`")),
+            };
+            Ok(Document {
+                stages,
+                group: pipeline.group,
+                sort: pipeline.sort,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::Rename;
+
+    #[test]
+    fn apply_built_transformation() {
+        let column = |index, header: &str| ColumnRef {
+            index,
+            header: header.to_string(),
+        };
+        let transformation = Transformation::new(vec![
+            column(1, "B").into(),
+            Rename::new("New A".to_string(), column(0, "A")).into(),
+        ]);
+        let input = "A,B\n1,2\n3,4\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+        let records = transformation.apply_reader(&mut reader).unwrap();
+        let records = records.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(transformation.headers(), ["B", "New A"]);
+        let records = records
+            .iter()
+            .map(|record| record.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(records, [["2", "1"], ["4", "3"]]);
+
+        let error = transformation.apply(&["1".to_string()]).err().unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "record has only 1 columns, missing column B: B",
+        );
+    }
+
+    #[test]
+    fn apply_stages() {
+        let yaml = r#"
+        stages:
+        - name: normalize
+          rules:
+          - transform: map
+            header: Country
+            column: "A: 国家"
+            values: { 中国: China }
+          - "B: Year"
+        - rules:
+          - transform: join
+            header: Summary
+            sep: " "
+            columns: ["A: Country", "B: Year"]
+        "#;
+        let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+        let headers = ["国家".to_string(), "Year".to_string()];
+        transformation.validate(&headers).unwrap();
+        assert!(transformation.stage("normalize").is_some());
+        assert_eq!(transformation.headers(), ["Summary"]);
+        let record = ["中国".to_string(), "2020".to_string()];
+        let outputs = transformation.apply_stages(&record).unwrap();
+        assert_eq!(outputs[0], ["China".into(), "2020".into()]);
+        assert_eq!(outputs[1], ["China 2020".into()]);
+
+        let yaml = yaml.replace("\"A: Country\"", "\"A: 国家\"");
+        let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+        let error = transformation.validate(&headers).err().unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "stage 1: invalid column reference: A: 国家",
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// A value of a field in the transformed data
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// Absence of value, which is written as an empty field in CSV
+    Null,
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+/// Type of the values a rule produces, which can be declared in the transformation file
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueType {
+    Text,
+    Integer,
+    Float,
+    Boolean,
+}
+
+impl Value {
+    /// Convert the value into the given type, where an empty value becomes null
+    pub fn convert(self, ty: ValueType) -> Result<Value> {
+        let text = match self {
+            Value::Text(text) => text,
+            Value::Null => return Ok(Value::Null),
+            value => value.to_string(),
+        };
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        let invalid = || anyhow!("`{}` is not a valid {} value", text, ty);
+        Ok(match ty {
+            ValueType::Text => Value::Text(text),
+            ValueType::Integer => Value::Integer(text.trim().parse().map_err(|_| invalid())?),
+            ValueType::Float => match text.trim().parse::<f64>() {
+                Ok(number) if number.is_finite() => Value::Float(number),
+                _ => return Err(invalid()),
+            },
+            ValueType::Boolean => match text.trim().to_lowercase().as_str() {
+                "true" | "yes" => Value::Boolean(true),
+                "false" | "no" => Value::Boolean(false),
+                _ => return Err(invalid()),
+            },
+        })
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::Text => "text",
+            ValueType::Integer => "integer",
+            ValueType::Float => "float",
+            ValueType::Boolean => "boolean",
+        })
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Text(s) => serializer.serialize_str(s),
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::Float(x) => serializer.serialize_f64(*x),
+            Value::Boolean(b) => serializer.serialize_bool(*b),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Text(s) => f.write_str(s),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Text(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Text(s.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformation;
+
+    #[test]
+    fn declared_types() {
+        let yaml = r#"
+        - column: "A: Age"
+          type: integer
+        - transform: map
+          header: Rust?
+          column: "B: Rustacean"
+          values: { 是: yes, 否: no }
+          type: boolean
+        - "C: Score"
+        "#;
+        let transformation = Transformation::from_yaml(yaml.as_bytes()).unwrap();
+        assert_eq!(transformation.headers(), ["Age", "Rust?", "Score"]);
+        let record = ["30", "是", "4.5"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transformation.apply(&record).unwrap(),
+            [Value::Integer(30), Value::Boolean(true), "4.5".into()],
+        );
+        let record = ["", "否", ""]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transformation.apply(&record).unwrap(),
+            [Value::Null, Value::Boolean(false), "".into()],
+        );
+        let record = ["thirty", "是", ""]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let error = transformation.apply(&record).err().unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "convert value of column `Age`: `thirty` is not a valid integer value",
+        );
+        assert_eq!(
+            Value::from("4.5").convert(ValueType::Float).unwrap(),
+            Value::Float(4.5),
+        );
+        assert!(Value::from("NaN").convert(ValueType::Float).is_err());
+        assert!(Value::from("-inf").convert(ValueType::Float).is_err());
+    }
+}